@@ -44,6 +44,30 @@ fn main() {
         safety_reasoning: &["* Takes no parameters.", "* Returns safe [`PxTask_t`]."],
     }];
 
+    let safe_event_functions = &[
+        SafeFunctionWrapper {
+            function_name: "PxAwaitEvents",
+            safety_reasoning: &[
+                "* Parameters are copied and checked by PXROS.",
+                "* Returns safe [`PxEvents_t`].",
+            ],
+        },
+        SafeFunctionWrapper {
+            function_name: "PxResetEvents",
+            safety_reasoning: &[
+                "* Parameters are copied and checked by PXROS.",
+                "* Returns safe [`PxEvents_t`].",
+            ],
+        },
+        SafeFunctionWrapper {
+            function_name: "PxTaskSignalEvents",
+            safety_reasoning: &[
+                "* Parameters are copied and checked by PXROS.",
+                "* Returns safe [`PxError_t`].",
+            ],
+        },
+    ];
+
     let safe_message_functions = &[
         SafeFunctionWrapper {
             function_name: "PxMsgAwaitRel",
@@ -199,6 +223,7 @@ fn main() {
     let safe_functions = [
         safe_error_functions.as_ref(),
         safe_task_functions.as_ref(),
+        safe_event_functions.as_ref(),
         safe_message_functions.as_ref(),
     ]
     .concat();
@@ -226,6 +251,8 @@ fn main() {
         .blocklist_item("PxMc_t")
         .blocklist_item("PxTask_t")
         .blocklist_item("PxPe_t")
+        .blocklist_item("PxDelay_t")
+        .blocklist_item("PxTo_t")
         .blocklist_item("PxMbx_t")
         .blocklist_item("PxMsg_t")
         .blocklist_item("PxTask_t")
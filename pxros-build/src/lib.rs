@@ -0,0 +1,141 @@
+//! Build script helpers for PXROS applications.
+//!
+//! The runtime crate `pxros` is `no_std` and built for the target, build
+//! scripts run on the host. Code only needed by build scripts lives here, so
+//! it neither ends up in the target image nor pulls in the kernel bindings.
+//!
+//! [write_fragment] emits the linker script fragment for the task sections
+//! declared with `pxros::task_section!`:
+//!
+//! ```ignore
+//! // build.rs
+//! let mut fragment = String::new();
+//! pxros_build::write_fragment(&mut fragment, &[LinkerSection::new("can", "RAM_CPU0")])?;
+//! std::fs::write(out_dir.join("pxros_sections.ld"), fragment)?;
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::fmt::{self, Write};
+
+/// Granularity of the TriCore MPU range bounds in bytes.
+///
+/// Matches `pxros::mem::region::REGION_GRANULARITY`, which a test of `pxros`
+/// checks.
+pub const REGION_GRANULARITY: u32 = 8;
+
+/// Placement of a task section declared with `pxros::task_section!`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LinkerSection<'a> {
+    /// Name passed to the macro.
+    pub name: &'a str,
+    /// Memory region the section is placed in at runtime.
+    pub region: &'a str,
+    /// Memory region the initial data is loaded from, `None` for a section
+    /// that is not loaded.
+    pub load_region: Option<&'a str>,
+}
+
+impl<'a> LinkerSection<'a> {
+    /// Places the section `name` in the memory region `region`.
+    ///
+    /// Without [`LinkerSection::load_from`] the section is `NOLOAD`.
+    pub const fn new(name: &'a str, region: &'a str) -> Self {
+        Self {
+            name,
+            region,
+            load_region: None,
+        }
+    }
+
+    /// Loads the initial data of the section from `load_region`.
+    pub const fn load_from(mut self, load_region: &'a str) -> Self {
+        self.load_region = Some(load_region);
+        self
+    }
+}
+
+/// Writes a linker script `SECTIONS` block for the task sections.
+///
+/// Start and end of each section are aligned to the MPU granularity, so its
+/// region passes the `pxros::mem::RegionSet` checks.
+///
+/// A section with a load region is placed there with `AT>` and
+/// `__pxros_<name>_load` is its load address. Startup code copies the bytes
+/// from `__pxros_<name>_start` to `__pxros_<name>_end` from there, like
+/// `.data`. A section without a load region is `NOLOAD`, it has no initial
+/// data and startup code has to zero it like `.bss`, so it may only hold
+/// zero-initialized statics.
+pub fn write_fragment(out: &mut impl Write, sections: &[LinkerSection]) -> fmt::Result {
+    writeln!(out, "SECTIONS")?;
+    writeln!(out, "{{")?;
+    for section in sections {
+        let name = section.name;
+        let kind = match section.load_region {
+            Some(_) => "",
+            None => " (NOLOAD)",
+        };
+        writeln!(out, "    .pxros.{name}{kind} : ALIGN({REGION_GRANULARITY})")?;
+        writeln!(out, "    {{")?;
+        writeln!(out, "        __pxros_{name}_start = .;")?;
+        writeln!(out, "        *(.pxros.{name} .pxros.{name}.*)")?;
+        writeln!(out, "        . = ALIGN({REGION_GRANULARITY});")?;
+        writeln!(out, "        __pxros_{name}_end = .;")?;
+        match section.load_region {
+            Some(load_region) => {
+                writeln!(out, "    }} > {} AT> {load_region}", section.region)?;
+                writeln!(out, "    __pxros_{name}_load = LOADADDR(.pxros.{name});")?;
+            }
+            None => writeln!(out, "    }} > {}", section.region)?,
+        }
+    }
+    writeln!(out, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_aligned_sections_with_symbols() {
+        let mut fragment = String::new();
+        write_fragment(
+            &mut fragment,
+            &[
+                LinkerSection::new("can", "RAM_CPU0"),
+                LinkerSection::new("eth", "RAM_CPU1").load_from("FLASH"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            fragment,
+            "SECTIONS\n\
+             {\n\
+             \x20   .pxros.can (NOLOAD) : ALIGN(8)\n\
+             \x20   {\n\
+             \x20       __pxros_can_start = .;\n\
+             \x20       *(.pxros.can .pxros.can.*)\n\
+             \x20       . = ALIGN(8);\n\
+             \x20       __pxros_can_end = .;\n\
+             \x20   } > RAM_CPU0\n\
+             \x20   .pxros.eth : ALIGN(8)\n\
+             \x20   {\n\
+             \x20       __pxros_eth_start = .;\n\
+             \x20       *(.pxros.eth .pxros.eth.*)\n\
+             \x20       . = ALIGN(8);\n\
+             \x20       __pxros_eth_end = .;\n\
+             \x20   } > RAM_CPU1 AT> FLASH\n\
+             \x20   __pxros_eth_load = LOADADDR(.pxros.eth);\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn writes_empty_block_without_sections() {
+        let mut fragment = String::new();
+        write_fragment(&mut fragment, &[]).unwrap();
+        assert_eq!(fragment, "SECTIONS\n{\n}\n");
+    }
+}
@@ -46,6 +46,8 @@ impl_kernel_handle! {
     PxMbx_t: PxMailbox,
     PxMsg_t: PxMessage,
     PxPe_t: PxPeriodicEvent,
+    PxDelay_t: PxDelay,
+    PxTo_t: PxTimeout,
     PxTask_t: PxTask,
     PxMc_t: PxMemoryClass,
     PxOpool_t: PxMemoryPool,
@@ -0,0 +1,148 @@
+//! Delay [PxDelay_t] and timeout [PxTo_t] objects.
+//!
+//! A [Delay] calls a handler once its duration elapsed, a [Timeout] signals
+//! events to the requesting task instead. Both are requested from an object
+//! pool, [`PxOpool_t::TASK`] unless specified otherwise, and released on drop.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::bindings::*;
+use crate::events::Events;
+use crate::time::Duration;
+use crate::PxResult;
+
+/// Handler called when a [Delay] elapsed.
+///
+/// Handlers run in handler context, so they may only use the `_Hnd` variants
+/// of the kernel API. The handler is safe to call with any argument, a
+/// handler interpreting it as a pointer has to ensure its validity itself.
+pub type DelayHandler = extern "C" fn(PxArg_t);
+
+/// Owned delay object, released on drop.
+#[must_use = "Dropping a delay releases it"]
+pub struct Delay {
+    handle: PxDelay_t,
+}
+
+impl Delay {
+    /// Requests a delay object from the default task object pool.
+    pub fn new() -> PxResult<Self> {
+        Self::request(PxOpool_t::default())
+    }
+
+    /// Requests a delay object from the object pool.
+    pub fn request(opool: impl Into<PxOpool_t>) -> PxResult<Self> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        let handle = unsafe { PxDelayRequest(opool.into()) }.checked()?;
+        Ok(Self { handle })
+    }
+
+    /// Returns the raw delay handle.
+    pub const fn handle(&self) -> PxDelay_t {
+        self.handle
+    }
+
+    /// Schedules `handler` to be called with `arg` after `delay`.
+    ///
+    /// Rescheduling replaces a pending schedule.
+    pub fn schedule(&self, delay: Duration, handler: DelayHandler, arg: PxArg_t) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS, `handler` is a safe function.
+        unsafe { PxDelaySched(self.handle, delay.into(), Some(handler), arg) }.into()
+    }
+
+    /// Cancels a pending schedule.
+    pub fn cancel(&self) -> PxResult<()> {
+        // # Safety
+        // Zero ticks removes the pending schedule, no handler is installed.
+        unsafe { PxDelaySched(self.handle, PxTicks_t(0), None, PxArg_t(0)) }.into()
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        let _ = self.cancel();
+        // # Safety
+        // The handle is owned by this delay and not used afterwards.
+        let _ = unsafe { PxDelayRelease(self.handle) };
+    }
+}
+
+impl defmt::Format for Delay {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Delay({})", self.handle)
+    }
+}
+
+/// Owned timeout object, released on drop.
+///
+/// The timeout is created stopped and signals its events to the requesting
+/// task once its duration elapsed after [`Timeout::start`].
+#[must_use = "Dropping a timeout releases it"]
+pub struct Timeout {
+    handle: PxTo_t,
+    events: Events,
+}
+
+impl Timeout {
+    /// Requests a timeout signalling `events` after `timeout` from the default task object pool.
+    pub fn new(timeout: Duration, events: Events) -> PxResult<Self> {
+        Self::request(PxOpool_t::default(), timeout, events)
+    }
+
+    /// Requests a timeout signalling `events` after `timeout` from the object pool.
+    pub fn request(
+        opool: impl Into<PxOpool_t>,
+        timeout: Duration,
+        events: Events,
+    ) -> PxResult<Self> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        let handle = unsafe { PxToRequest(opool.into(), timeout.into(), events) }.checked()?;
+        Ok(Self { handle, events })
+    }
+
+    /// Returns the raw timeout handle.
+    pub const fn handle(&self) -> PxTo_t {
+        self.handle
+    }
+
+    /// Returns the events signalled on expiry.
+    pub const fn events(&self) -> Events {
+        self.events
+    }
+
+    /// Starts the timeout, restarting it if it is already running.
+    pub fn start(&self) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxToStart(self.handle) }.into()
+    }
+
+    /// Stops the timeout.
+    pub fn stop(&self) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxToStop(self.handle) }.into()
+    }
+}
+
+impl Drop for Timeout {
+    fn drop(&mut self) {
+        // # Safety
+        // The handle is owned by this timeout and not used afterwards.
+        unsafe {
+            let _ = PxToStop(self.handle);
+            let _ = PxToRelease(self.handle);
+        }
+    }
+}
+
+impl defmt::Format for Timeout {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Timeout({}, events: {})", self.handle, self.events)
+    }
+}
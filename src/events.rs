@@ -0,0 +1,539 @@
+//! Utilities for working with task events [PxEvents_t].
+//!
+//! Every task owns 32 event bits. [Events] is used both as the mask passed to
+//! the kernel and as the set of events it returns.
+//!
+//! Named events are declared with [event_set!](crate::event_set), which
+//! allocates the bits at compile time and yields a typed [EventSet].
+//! [task_events!](crate::task_events) composes the sets used by one task and
+//! rejects sets sharing bits at compile time.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::marker::PhantomData;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use defmt::Formatter;
+
+use crate::bindings::*;
+use crate::PxResult;
+
+/// Type export for working with [PxEvents_t]
+pub type Events = PxEvents_t;
+
+impl Events {
+    /// Mask without any event.
+    pub const NONE: Events = PxEvents_t(0);
+    /// Mask with all 32 events.
+    pub const ALL: Events = PxEvents_t(u32::MAX);
+
+    /// Creates a mask with the single event `bit`.
+    ///
+    /// Panics if `bit` is not below 32.
+    pub const fn from_bit(bit: u32) -> Self {
+        assert!(bit < u32::BITS, "PXROS tasks only have 32 events");
+        PxEvents_t(1 << bit)
+    }
+
+    /// Creates a mask from raw bits.
+    pub const fn from_bits(bits: u32) -> Self {
+        PxEvents_t(bits)
+    }
+
+    /// Returns the raw bits of the mask.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Checks if no event is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Checks if all events of `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Checks if any event of `other` is set.
+    pub const fn intersects(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns an iterator over the set events, each as a single-event mask.
+    pub fn iter(&self) -> EventsIter {
+        EventsIter { remaining: *self }
+    }
+
+    /// Blocks until any event of the mask arrives.
+    ///
+    /// Returns and resets the events that arrived.
+    pub fn await_any(self) -> Events {
+        PxAwaitEvents(self)
+    }
+
+    /// Blocks until all events of the mask arrived.
+    ///
+    /// Events of the mask that arrive while waiting are reset.
+    pub fn await_all(self) -> Events {
+        let mut received = Events::NONE;
+        while !received.contains(self) {
+            received |= PxAwaitEvents(self & !received);
+        }
+        received
+    }
+
+    /// Signals the events of the mask to `task`.
+    pub fn signal(self, task: PxTask_t) -> PxResult<()> {
+        PxTaskSignalEvents(task, self).into()
+    }
+
+    /// Resets the events of the mask for the calling task.
+    ///
+    /// Returns the events of the mask that were pending. The kernel result is
+    /// masked, as it may include other pending events.
+    pub fn reset(self) -> Events {
+        PxResetEvents(self) & self
+    }
+
+    /// Returns the pending events of the calling task without resetting them.
+    ///
+    /// The kernel has no call to read the events, so they are reset and
+    /// signalled to the calling task again. Events arriving in between stay
+    /// pending.
+    pub fn pending() -> PxResult<Events> {
+        let pending = Events::ALL.reset();
+        if !pending.is_empty() {
+            pending.signal(PxGetId())?;
+        }
+        Ok(pending)
+    }
+}
+
+impl PartialEq for Events {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Events {}
+
+impl BitOr for Events {
+    type Output = Events;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        PxEvents_t(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Events {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Events {
+    type Output = Events;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        PxEvents_t(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Events {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl Not for Events {
+    type Output = Events;
+
+    fn not(self) -> Self::Output {
+        PxEvents_t(!self.0)
+    }
+}
+
+impl IntoIterator for Events {
+    type Item = Events;
+    type IntoIter = EventsIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl defmt::Format for Events {
+    fn format(&self, fmt: Formatter) {
+        defmt::write!(fmt, "{:032b}", self.0)
+    }
+}
+
+/// Iterator over the set events of an [Events] mask, lowest bit first.
+#[derive(Clone, Debug)]
+pub struct EventsIter {
+    remaining: Events,
+}
+
+impl Iterator for EventsIter {
+    type Item = Events;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+        let lowest = PxEvents_t(self.remaining.0 & self.remaining.0.wrapping_neg());
+        self.remaining.0 &= !lowest.0;
+        Some(lowest)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.remaining.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for EventsIter {}
+
+/// Event names declared with [event_set!](crate::event_set).
+///
+/// The names of an implementor occupy the contiguous bits starting at
+/// [`EventName::FIRST_BIT`], in declaration order.
+pub trait EventName: Copy + 'static {
+    /// Bit of the first name.
+    const FIRST_BIT: u32;
+    /// Names in declaration order.
+    const NAMES: &'static [&'static str];
+    /// First bit after the last name, used to chain event sets.
+    const END_BIT: u32 = Self::FIRST_BIT + Self::NAMES.len() as u32;
+    /// Mask with the bits of all names.
+    const MASK: Events = PxEvents_t(((1u64 << Self::END_BIT) - (1u64 << Self::FIRST_BIT)) as u32);
+
+    /// Returns the position of the name in the declaration.
+    fn offset(self) -> u32;
+
+    /// Returns the name at `offset` in the declaration.
+    fn from_offset(offset: u32) -> Option<Self>;
+
+    /// Returns the single-event mask of the name.
+    fn events(self) -> Events {
+        Events::from_bit(Self::FIRST_BIT + self.offset())
+    }
+
+    /// Returns the declared name.
+    fn name(self) -> &'static str {
+        Self::NAMES[self.offset() as usize]
+    }
+}
+
+/// Typed set of events declared with [event_set!](crate::event_set).
+pub struct EventSet<E> {
+    events: Events,
+    marker: PhantomData<E>,
+}
+
+impl<E: EventName> EventSet<E> {
+    /// Creates an empty set.
+    pub const fn empty() -> Self {
+        Self {
+            events: Events::NONE,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a set with all declared events.
+    pub const fn all() -> Self {
+        Self {
+            events: E::MASK,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a set from raw events, ignoring events that are not declared in `E`.
+    pub fn from_events(events: Events) -> Self {
+        Self {
+            events: events & E::MASK,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the raw events of the set.
+    pub const fn events(&self) -> Events {
+        self.events
+    }
+
+    /// Checks if no event is set.
+    pub const fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Checks if `event` is set.
+    pub fn contains(&self, event: E) -> bool {
+        self.events.contains(event.events())
+    }
+
+    /// Adds `event` to the set.
+    pub fn insert(&mut self, event: E) {
+        self.events |= event.events();
+    }
+
+    /// Removes `event` from the set.
+    pub fn remove(&mut self, event: E) {
+        self.events &= !event.events();
+    }
+
+    /// Returns an iterator over the set events in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = E> {
+        self.events
+            .iter()
+            .filter_map(|event| E::from_offset(event.0.trailing_zeros() - E::FIRST_BIT))
+    }
+
+    /// Blocks until any event of the set arrives, see [`Events::await_any`].
+    pub fn await_any(self) -> Self {
+        Self::from_events(self.events.await_any())
+    }
+
+    /// Blocks until all events of the set arrived, see [`Events::await_all`].
+    pub fn await_all(self) -> Self {
+        Self::from_events(self.events.await_all())
+    }
+
+    /// Signals the events of the set to `task`.
+    pub fn signal(self, task: PxTask_t) -> PxResult<()> {
+        self.events.signal(task)
+    }
+
+    /// Resets the events of the set for the calling task, see [`Events::reset`].
+    pub fn reset(self) -> Self {
+        Self::from_events(self.events.reset())
+    }
+}
+
+impl<E> Clone for EventSet<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for EventSet<E> {}
+
+impl<E> PartialEq for EventSet<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.events == other.events
+    }
+}
+
+impl<E> Eq for EventSet<E> {}
+
+impl<E: EventName> Default for EventSet<E> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<E: EventName> From<E> for EventSet<E> {
+    fn from(event: E) -> Self {
+        Self {
+            events: event.events(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> From<EventSet<E>> for Events {
+    fn from(set: EventSet<E>) -> Self {
+        set.events
+    }
+}
+
+impl<E> BitOr for EventSet<E> {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            events: self.events | rhs.events,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E: EventName> BitOr<E> for EventSet<E> {
+    type Output = Self;
+
+    fn bitor(self, rhs: E) -> Self::Output {
+        self | Self::from(rhs)
+    }
+}
+
+impl<E> BitOrAssign for EventSet<E> {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.events |= rhs.events;
+    }
+}
+
+impl<E> BitAnd for EventSet<E> {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self {
+            events: self.events & rhs.events,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> BitAndAssign for EventSet<E> {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.events &= rhs.events;
+    }
+}
+
+impl<E: EventName> defmt::Format for EventSet<E> {
+    fn format(&self, fmt: Formatter) {
+        defmt::write!(fmt, "{{");
+        for (index, event) in self.iter().enumerate() {
+            if index > 0 {
+                defmt::write!(fmt, ", ");
+            }
+            defmt::write!(fmt, "{=str}", event.name());
+        }
+        defmt::write!(fmt, "}}");
+    }
+}
+
+/// Declares named task events and allocates their bits at compile time.
+///
+/// The names are assigned consecutive bits in declaration order, starting at
+/// bit 0 or after the last bit of the set named with `after`. Chaining the
+/// sets of all modules used by a task keeps their bits apart. Reusing a name
+/// or declaring more than 32 events in a chain fails to compile.
+///
+/// Sets are declared independently of the tasks using them, so two sets
+/// starting at the same bit are not detected here. Every task lists its sets
+/// with [task_events!](crate::task_events), which checks that they are disjoint.
+///
+/// ```ignore
+/// pxros::event_set! {
+///     /// Events of the network stack.
+///     pub enum NetEvents {
+///         Rx,
+///         Tx,
+///     }
+/// }
+///
+/// pxros::event_set! {
+///     /// Events of the CAN driver, allocated after the network events.
+///     pub enum CanEvents after NetEvents {
+///         Frame,
+///     }
+/// }
+///
+/// let received = (NetEvents::Rx | NetEvents::Tx).await_any();
+/// if received.contains(NetEvents::Rx) {
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! event_set {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident $(after $after:path)? {
+            $($(#[$variant_meta:meta])* $variant:ident),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        #[repr(u32)]
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant,)*
+        }
+
+        impl $crate::events::EventName for $name {
+            const FIRST_BIT: u32 = 0 $(+ <$after as $crate::events::EventName>::END_BIT)?;
+            const NAMES: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+            fn offset(self) -> u32 {
+                self as u32
+            }
+
+            fn from_offset(offset: u32) -> ::core::option::Option<Self> {
+                [$(Self::$variant),*].get(offset as usize).copied()
+            }
+        }
+
+        const _: () = assert!(
+            <$name as $crate::events::EventName>::END_BIT <= 32,
+            concat!("`", stringify!($name), "` exceeds the 32 events of a PXROS task"),
+        );
+
+        impl ::core::ops::BitOr for $name {
+            type Output = $crate::events::EventSet<$name>;
+
+            fn bitor(self, rhs: Self) -> Self::Output {
+                $crate::events::EventSet::from(self) | rhs
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::events::Events {
+            fn from(event: $name) -> Self {
+                $crate::events::EventName::events(event)
+            }
+        }
+
+        impl $crate::__private::defmt::Format for $name {
+            fn format(&self, fmt: $crate::__private::defmt::Formatter) {
+                $crate::__private::defmt::Format::format(
+                    &$crate::events::EventName::name(*self),
+                    fmt,
+                )
+            }
+        }
+    };
+}
+
+/// Composes the event sets used by one task and checks that they are disjoint.
+///
+/// Defines a constant with the mask of all events of the task. Compilation
+/// fails if two of the sets share a bit, e.g. because both were declared
+/// `after` the same set or independently of each other. Listing the sets of
+/// every task this way is required to keep their bits apart.
+///
+/// ```ignore
+/// pxros::task_events! {
+///     /// Events of the gateway task.
+///     pub GATEWAY_EVENTS = [NetEvents, CanEvents];
+/// }
+///
+/// let received = GATEWAY_EVENTS.await_any();
+/// ```
+#[macro_export]
+macro_rules! task_events {
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident = [$($set:path),+ $(,)?];
+    ) => {
+        $(#[$meta])*
+        $vis const $name: $crate::events::Events = $crate::events::Events::from_bits(
+            0 $(| <$set as $crate::events::EventName>::MASK.bits())+
+        );
+
+        $crate::task_events!(@disjoint $name; $($set),+);
+    };
+    (@disjoint $name:ident; $first:path $(, $rest:path)*) => {
+        $(
+            const _: () = assert!(
+                !<$first as $crate::events::EventName>::MASK
+                    .intersects(<$rest as $crate::events::EventName>::MASK),
+                concat!(
+                    "`", stringify!($first), "` and `", stringify!($rest),
+                    "` of `", stringify!($name), "` share event bits",
+                ),
+            );
+        )*
+        $crate::task_events!(@disjoint $name; $($rest),*);
+    };
+    (@disjoint $name:ident;) => {};
+}
@@ -0,0 +1,199 @@
+//! Allocation-free async executor parking the task on PXROS events.
+//!
+//! An [Executor] runs a fixed number of futures inside one PXROS task. Wakers
+//! mark their future as ready and signal the reserved wake event to the
+//! owning task, which blocks in [`Events::await_any`] while no future is
+//! ready.
+//!
+//! The executor is placed in a `static`, so wakers stay valid however long
+//! they are kept.
+//!
+//! Futures waiting for a message, e.g. [`Mailbox::recv`](crate::msg::Mailbox::recv),
+//! install a mailbox handler with [PxMbxInstallHnd] that wakes their slot
+//! whenever a message is sent to the mailbox. Every awaited mailbox therefore
+//! wakes the task, however many futures wait at once.
+//!
+//! ```ignore
+//! static EXECUTOR: Executor<2> = Executor::new(Events::from_bit(31));
+//!
+//! extern "C" fn task_main(_task: PxTask_t, _mbx: PxMbx_t, _events: PxEvents_t) {
+//!     let control = core::pin::pin!(control_loop());
+//!     let telemetry = core::pin::pin!(telemetry_loop());
+//!     EXECUTOR.run([control, telemetry]);
+//! }
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+
+use crate::bindings::*;
+use crate::events::Events;
+use crate::msg::Mailbox;
+use crate::PxResult;
+
+/// Executor for `N` futures of one PXROS task.
+pub struct Executor<const N: usize> {
+    wake: Events,
+    slots: [Slot; N],
+}
+
+impl<const N: usize> Executor<N> {
+    /// Creates an executor that uses `wake` as its reserved wake event.
+    ///
+    /// The event must not be used otherwise by the task running the executor.
+    pub const fn new(wake: Events) -> Self {
+        Self {
+            wake,
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Polls the futures until all of them completed.
+    ///
+    /// The calling task becomes the owner of the executor. While no future is
+    /// ready, the task blocks on the wake event. An executor must only be run
+    /// by one task at a time.
+    pub fn run(&'static self, mut futures: [Pin<&mut dyn Future<Output = ()>>; N]) {
+        let task = PxGetId();
+        for slot in &self.slots {
+            slot.task.store(task.as_raw(), Ordering::Relaxed);
+            slot.wake.store(self.wake.bits(), Ordering::Relaxed);
+            slot.ready.store(true, Ordering::Release);
+        }
+
+        let mut completed = [false; N];
+        loop {
+            let mut polled = false;
+            for (index, future) in futures.iter_mut().enumerate() {
+                let slot = &self.slots[index];
+                if completed[index] || !slot.ready.load(Ordering::Acquire) {
+                    continue;
+                }
+                // Reset before polling, so a wake-up during the poll is kept.
+                slot.ready.store(false, Ordering::Release);
+                polled = true;
+
+                let waker = slot.waker();
+                let mut context = Context::from_waker(&waker);
+                if future.as_mut().poll(&mut context).is_ready() {
+                    completed[index] = true;
+                }
+            }
+
+            if completed.iter().all(|completed| *completed) {
+                return;
+            }
+            if !polled {
+                // Wake events signalled since the last wait are still pending,
+                // so no wake-up is lost between polling and waiting.
+                self.wake.await_any();
+            }
+        }
+    }
+}
+
+/// Returns the executor slot a waker belongs to, if it was created by an [Executor].
+pub(crate) fn slot_of(waker: &Waker) -> Option<&'static Slot> {
+    if core::ptr::eq(waker.vtable(), &VTABLE) {
+        // # Safety
+        // Wakers with the slot vtable are only created from `'static` slots.
+        Some(unsafe { &*(waker.data() as *const Slot) })
+    } else {
+        None
+    }
+}
+
+/// Waker functions of a [Slot], a `static` to give it a unique address.
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    Slot::waker_clone,
+    Slot::waker_wake,
+    Slot::waker_wake,
+    Slot::waker_drop,
+);
+
+/// Per-future state referenced by its wakers.
+///
+/// Apart from `ready`, the state is only written by the owning task.
+pub(crate) struct Slot {
+    ready: AtomicBool,
+    task: AtomicU32,
+    wake: AtomicU32,
+}
+
+impl Slot {
+    /// Slot before the executor is run.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Slot = Slot {
+        ready: AtomicBool::new(false),
+        task: AtomicU32::new(0),
+        wake: AtomicU32::new(0),
+    };
+
+    /// Wakes the slot whenever a message is sent to `mailbox`, until the handler is removed.
+    ///
+    /// Replaces a handler installed on the mailbox before.
+    pub(crate) fn watch(&'static self, mailbox: Mailbox) -> PxResult<()> {
+        // # Safety
+        // The argument points to the slot, which is `'static`.
+        unsafe { mailbox.install_handler(Self::handler, PxArg_t(self as *const Slot as _)) }
+    }
+
+    fn waker(&'static self) -> Waker {
+        // # Safety
+        // The slot is `'static` and the vtable functions uphold the RawWaker contract.
+        unsafe { Waker::from_raw(RawWaker::new(self as *const Slot as *const (), &VTABLE)) }
+    }
+
+    /// Marks the future as ready and wakes the owning task.
+    ///
+    /// Wakers may be used from other tasks, but not from handler context.
+    fn wake(&self) {
+        self.ready.store(true, Ordering::Release);
+        let _ = self.wake_events().signal(self.task());
+    }
+
+    /// Mailbox handler marking the slot `arg` points to as ready and waking its task.
+    ///
+    /// # Safety
+    /// `arg` must point to a `'static` [Slot].
+    unsafe extern "C" fn handler(message: PxMsg_t, arg: PxArg_t) -> PxMsg_t {
+        // # Safety
+        // The caller guarantees that `arg` points to a `'static` slot.
+        let slot = unsafe { &*(arg.0 as usize as *const Slot) };
+        slot.ready.store(true, Ordering::Release);
+        // # Safety
+        // Parameters are copied and checked by PXROS, the handler runs in handler context.
+        let _ = unsafe { PxTaskSignalEvents_Hnd(slot.task(), slot.wake_events()) };
+        message
+    }
+
+    fn task(&self) -> PxTask_t {
+        PxTask_t::from_raw(self.task.load(Ordering::Relaxed))
+    }
+
+    fn wake_events(&self) -> Events {
+        Events::from_bits(self.wake.load(Ordering::Relaxed))
+    }
+
+    unsafe fn waker_clone(data: *const ()) -> RawWaker {
+        RawWaker::new(data, &VTABLE)
+    }
+
+    unsafe fn waker_wake(data: *const ()) {
+        (*(data as *const Slot)).wake();
+    }
+
+    unsafe fn waker_drop(_data: *const ()) {}
+}
+
+impl<const N: usize> defmt::Format for Executor<N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Executor(futures: {}, wake: {})", N, self.wake)
+    }
+}
@@ -0,0 +1,165 @@
+//! [embedded-hal](embedded_hal) implementations on top of PXROS.
+//!
+//! Enabled with the `embedded-hal` feature.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use embedded_hal::delay::DelayNs;
+
+use crate::bindings::*;
+use crate::delay::Delay;
+use crate::events::Events;
+use crate::time::{ticks_per_second, Duration, Instant};
+use crate::PxResult;
+
+/// Task-blocking [DelayNs] implementation.
+///
+/// Delays of at least one tick block the calling task on a [Delay] object
+/// whose handler signals `events`, so other tasks keep running. Shorter
+/// delays spin, calibrated against the tick counter on construction. If the
+/// kernel refuses to schedule the delay, the task spins on the tick counter
+/// instead.
+pub struct TaskDelay {
+    delay: Delay,
+    signal: Signal,
+    nanos_per_tick: u32,
+    spins_per_tick: u32,
+}
+
+/// Target of the delay handler.
+struct Signal {
+    task: PxTask_t,
+    events: Events,
+}
+
+impl TaskDelay {
+    /// Creates a delay signalling `events` from the default task object pool.
+    ///
+    /// The events are reserved for the delay and must not be used otherwise by the calling task.
+    /// See [`TaskDelay::request`] for the errors.
+    pub fn new(events: Events) -> PxResult<Self> {
+        Self::request(PxOpool_t::default(), events)
+    }
+
+    /// Creates a delay signalling `events` from the object pool.
+    ///
+    /// The events are reserved for the delay and must not be used otherwise by the calling task.
+    /// Fails with `PXERR_EVENT_ZERO` if `events` is empty, as the task could
+    /// not be woken, and with `PXERR_REQUEST_ILLEGAL_PARAMETER` if the tick
+    /// counter does not advance during calibration.
+    pub fn request(opool: impl Into<PxOpool_t>, events: Events) -> PxResult<Self> {
+        if events.is_empty() {
+            return Err(PxError_t::PXERR_EVENT_ZERO);
+        }
+        let spins_per_tick =
+            calibrate_spins_per_tick().ok_or(PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER)?;
+        Ok(Self {
+            delay: Delay::request(opool)?,
+            signal: Signal {
+                task: PxGetId(),
+                events,
+            },
+            nanos_per_tick: (1_000_000_000 / ticks_per_second()).max(1),
+            spins_per_tick,
+        })
+    }
+
+    /// Blocks the calling task for at least `duration`.
+    fn block(&mut self, duration: Duration) {
+        // A delay of n ticks may start just before a tick, so wait one more to
+        // guarantee the requested duration.
+        let duration = duration
+            .checked_add(Duration::from_ticks(1))
+            .unwrap_or(Duration::MAX);
+        self.signal.task = PxGetId();
+        self.signal.events.reset();
+        let arg = PxArg_t(&self.signal as *const Signal as _);
+        if self.delay.schedule(duration, signal_handler, arg).is_ok() {
+            self.signal.events.await_any();
+        } else {
+            // Without a scheduled delay the task spins on the tick counter, so
+            // the delay still lasts at least `duration`.
+            let start = Instant::now().as_ticks();
+            while Instant::now().as_ticks().wrapping_sub(start) < duration.as_ticks() {
+                core::hint::spin_loop();
+            }
+        }
+        // The handler must not run anymore once `self.signal` may move.
+        let _ = self.delay.cancel();
+    }
+
+    /// Spins for at least `nanos`, which should be shorter than a tick.
+    fn spin(&self, nanos: u32) {
+        let spins =
+            (nanos as u64 * self.spins_per_tick as u64).div_ceil(self.nanos_per_tick as u64);
+        for _ in 0..spins {
+            core::hint::black_box(Instant::now());
+        }
+    }
+}
+
+impl DelayNs for TaskDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        if ns < self.nanos_per_tick {
+            self.spin(ns);
+        } else {
+            self.block(Duration::from_ticks(ns.div_ceil(self.nanos_per_tick)));
+        }
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        match us.checked_mul(1_000) {
+            Some(ns) => self.delay_ns(ns),
+            None => self.block(Duration::from_micros(us)),
+        }
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.block(Duration::from_millis(ms));
+    }
+}
+
+/// Iterations after which the calibration stops waiting for a tick edge,
+/// far longer than a tick at the usual tick rates.
+const MAX_CALIBRATION_SPINS: u32 = 1 << 26;
+
+/// Counts the iterations of the spin loop body that fit into one tick.
+///
+/// Returns `None` if the tick counter does not advance, for example because
+/// the system timer is not running.
+fn calibrate_spins_per_tick() -> Option<u32> {
+    // Align to a tick edge first, then count up to the next one.
+    let start = Instant::now();
+    let mut spins = 0u32;
+    while Instant::now() == start {
+        spins += 1;
+        if spins == MAX_CALIBRATION_SPINS {
+            return None;
+        }
+    }
+    let start = Instant::now();
+    let mut spins = 0u32;
+    while core::hint::black_box(Instant::now()) == start {
+        spins += 1;
+        if spins == MAX_CALIBRATION_SPINS {
+            return None;
+        }
+    }
+    Some(spins)
+}
+
+/// Signals the events of the [Signal] `arg` points to.
+///
+/// Only scheduled by [`TaskDelay::block`], which cancels the schedule before
+/// its [Signal] may move.
+extern "C" fn signal_handler(arg: PxArg_t) {
+    // # Safety
+    // `arg` points to the `Signal` of the `TaskDelay` that scheduled the handler,
+    // which stays in place until the schedule is cancelled.
+    let signal = unsafe { &*(arg.0 as usize as *const Signal) };
+    // # Safety
+    // Parameters are copied and checked by PXROS, the handler runs in handler context.
+    let _ = unsafe { PxTaskSignalEvents_Hnd(signal.task, signal.events) };
+}
@@ -8,14 +8,50 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 #![cfg_attr(not(test), no_std)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
-use bindings::PxError_t;
-use defmt::Formatter;
+#[cfg(feature = "allocator-api")]
+extern crate alloc;
 
-use crate::bindings::PxEvents_t;
+use bindings::PxError_t;
 
 pub mod bindings;
+pub mod delay;
+pub mod events;
+pub mod executor;
+#[cfg(feature = "embedded-hal")]
+pub mod hal;
+pub mod linker;
 pub mod mem;
+pub mod msg;
+pub mod rpc;
+pub mod select;
+#[cfg(feature = "stack-paint")]
+pub mod stack;
+pub mod task;
+pub mod ticker;
+pub mod time;
+
+#[doc(hidden)]
+pub mod __private {
+    //! Re-exports and helpers used by the macros of this crate.
+    pub use defmt;
+
+    /// Writes the name of a generated struct and opens its fields.
+    pub fn format_open(fmt: defmt::Formatter, name: &'static str) {
+        defmt::write!(fmt, "{=str} {{", name)
+    }
+
+    /// Writes one field of a generated struct.
+    pub fn format_field<T: defmt::Format>(fmt: defmt::Formatter, name: &'static str, value: &T) {
+        defmt::write!(fmt, " {=str}: {}", name, value)
+    }
+
+    /// Closes the fields of a generated struct.
+    pub fn format_close(fmt: defmt::Formatter) {
+        defmt::write!(fmt, " }}")
+    }
+}
 
 /// Specialized result for a [PxError_t] with utility
 /// methods from/into
@@ -48,9 +84,3 @@ impl From<u32> for PxError_t {
         }
     }
 }
-
-impl defmt::Format for PxEvents_t {
-    fn format(&self, fmt: Formatter) {
-        defmt::write!(fmt, "{:032b}", self.0)
-    }
-}
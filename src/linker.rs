@@ -0,0 +1,104 @@
+//! Task data sections declared in Rust and placed by the linker.
+//!
+//! [task_section!](crate::task_section) places statics into the section
+//! `.pxros.<name>` and derives a [MemoryRegion](crate::mem::MemoryRegion)
+//! from the symbols `__pxros_<name>_start` and `__pxros_<name>_end`.
+//! `write_fragment` of the `pxros-build` crate emits the linker script
+//! fragment defining the section and its symbols from a build script, so
+//! placement and protection are generated from the same name:
+//!
+//! ```ignore
+//! // src/can.rs
+//! pxros::task_section! {
+//!     pub CanData = "can" {
+//!         static mut RX_FRAMES: [Frame; 16] = [Frame::EMPTY; 16];
+//!     }
+//! }
+//! let region = CanData::region(PxProtectType_t::WRProtection);
+//!
+//! // build.rs
+//! let mut fragment = String::new();
+//! pxros_build::write_fragment(&mut fragment, &[LinkerSection::new("can", "RAM_CPU0")])?;
+//! std::fs::write(out_dir.join("pxros_sections.ld"), fragment)?;
+//! ```
+//!
+//! A section placed with `LinkerSection::load_from` holds initialized data,
+//! the startup code has to copy it from `__pxros_<name>_load` like `.data`.
+//! Other sections are `NOLOAD` and have to be zeroed like `.bss`, so their
+//! statics must be zero-initialized.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+/// Declares statics placed in a task data section and a type describing the section.
+///
+/// The braces only accept `static` and `static mut` items.
+/// The generated type provides `SECTION`, the section name, and
+/// `region(protection)`, the [MemoryRegion](crate::mem::MemoryRegion)
+/// spanning the section. See the [module documentation](crate::linker).
+#[macro_export]
+macro_rules! task_section {
+    (@items $section:literal) => {};
+    (
+        @items $section:literal
+        $(#[$meta:meta])* $vis:vis static mut $item:ident: $ty:ty = $init:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        #[link_section = concat!(".pxros.", $section)]
+        $vis static mut $item: $ty = $init;
+        $crate::task_section!(@items $section $($rest)*);
+    };
+    (
+        @items $section:literal
+        $(#[$meta:meta])* $vis:vis static $item:ident: $ty:ty = $init:expr;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        #[link_section = concat!(".pxros.", $section)]
+        $vis static $item: $ty = $init;
+        $crate::task_section!(@items $section $($rest)*);
+    };
+    (@items $section:literal $($rest:tt)+) => {
+        compile_error!("task_section! only accepts `static` and `static mut` items");
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident = $section:literal {
+            $($items:tt)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $name {
+            /// Name of the linker section.
+            pub const SECTION: &'static str = concat!(".pxros.", $section);
+
+            /// Returns the memory region spanning the section.
+            pub fn region(
+                protection: $crate::bindings::PxProtectType_t,
+            ) -> $crate::mem::MemoryRegion {
+                extern "C" {
+                    #[link_name = concat!("__pxros_", $section, "_start")]
+                    static START: u8;
+                    #[link_name = concat!("__pxros_", $section, "_end")]
+                    static END: u8;
+                }
+                // # Safety
+                // Only the addresses of the linker symbols are taken.
+                #[allow(unused_unsafe)]
+                let (start, end) = unsafe {
+                    (
+                        ::core::ptr::addr_of!(START) as usize as u32,
+                        ::core::ptr::addr_of!(END) as usize as u32,
+                    )
+                };
+                $crate::mem::MemoryRegion::new(start..end, protection)
+            }
+        }
+
+        $crate::task_section!(@items $section $($items)*);
+    };
+}
@@ -5,8 +5,10 @@
 //! usage of object pools to allocate kernel objects and memory classes to
 //! allocate heap.
 //!
-//! For now, we only expose the types but only defer to the default impl
-//! when using them.
+//! The default object pools and memory classes are exposed as constants,
+//! [ObjectPool] and [MemoryClass] create further ones at runtime. With the
+//! `alloc` feature, `MemClassAllocator` serves heap allocations from a memory
+//! class.
 //!
 //! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
 //!
@@ -15,6 +17,21 @@
 use core::ops::Range;
 
 use super::bindings::*;
+use crate::PxResult;
+
+#[cfg(feature = "alloc")]
+pub mod allocator;
+pub mod class;
+pub mod pool;
+pub mod region;
+
+#[cfg(feature = "allocator-api")]
+pub use allocator::MemClass;
+#[cfg(feature = "alloc")]
+pub use allocator::MemClassAllocator;
+pub use class::{MemoryClass, MemoryClassBuilder, MemoryStats};
+pub use pool::ObjectPool;
+pub use region::{RegionError, RegionSet};
 
 impl PxOpool_t {
     /// Default global object pool
@@ -60,6 +77,7 @@ impl MemoryRegion {
     /// Constructs an invalid, zeroed [`MemoryRegion`].
     ///
     /// This is typically used as the terminator or an array of [`MemoryRegion`] used in PXROS.
+    /// [RegionSet] adds it automatically.
     pub const fn zeroed() -> Self {
         MemoryRegion {
             lowerBound: 0,
@@ -70,8 +88,10 @@ impl MemoryRegion {
 }
 
 /// Privileges of a task for accessing peripheral blocks.
+///
+/// Set with [`TaskBuilder::privileges`](crate::task::TaskBuilder::privileges).
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
 pub enum Privileges {
     /// User 0 mode
     NoDirectAccess = 0,
@@ -84,16 +104,144 @@ pub enum Privileges {
 /// Utility type to work with [PxStackSpec_T] definitions
 pub type StackSpec = PxStackSpec_T;
 
+/// Alignment required for task and interrupt stacks.
+const STACK_ALIGN: usize = 8;
+
+/// Memory of a pre-allocated stack of `N` words, aligned as the kernel requires.
+///
+/// Handed to a task with [`Stack::from_static`]:
+///
+/// ```ignore
+/// static mut CAN_STACK: StackMemory<256> = StackMemory::new();
+/// let stack = Stack::from_static(unsafe { &mut *core::ptr::addr_of_mut!(CAN_STACK) })?;
+/// ```
+#[repr(C, align(8))]
+pub struct StackMemory<const N: usize>([PxInt_t; N]);
+
+const _: () = assert!(core::mem::align_of::<StackMemory<1>>() == STACK_ALIGN);
+
+impl<const N: usize> StackMemory<N> {
+    /// Creates zeroed stack memory.
+    pub const fn new() -> Self {
+        Self([PxInt_t(0); N])
+    }
+}
+
+impl<const N: usize> Default for StackMemory<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Task or interrupt stack, consumed by the [TaskBuilder](crate::task::TaskBuilder).
+///
+/// Unlike the plain [StackSpec], a stack is not `Copy`, so pre-allocated
+/// stack memory is only ever used by one task.
+#[derive(Debug)]
+pub struct Stack {
+    spec: StackSpec,
+}
+
+impl Stack {
+    /// Allocates `size` bytes from the memory class when the task is created.
+    ///
+    /// Fails if `size` is zero or not a multiple of [PxInt_t].
+    pub fn alloc(size: u32, mem_class: PxMc_t) -> PxResult<Self> {
+        StackSpec::new(size, mem_class).map(|spec| Self { spec })
+    }
+
+    /// Uses pre-allocated stack memory, the stack falls from its end.
+    ///
+    /// Fails if the memory is empty. With the `stack-paint` feature, the
+    /// memory is painted for [`stack_high_water`](crate::stack::stack_high_water).
+    pub fn from_static<const N: usize>(memory: &'static mut StackMemory<N>) -> PxResult<Self> {
+        if N == 0 {
+            return Err(PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER);
+        }
+        let size = u32::try_from(N).map_err(|_| PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER)?;
+        let stack = &mut memory.0;
+        // # Safety
+        // The memory is exclusively borrowed and not yet used by a task.
+        #[cfg(feature = "stack-paint")]
+        unsafe {
+            crate::stack::paint(stack.as_mut_ptr().cast(), N);
+        }
+        // # Safety
+        // The pointer to the end of the memory stays within its allocation.
+        let top = unsafe { stack.as_mut_ptr().add(N) };
+        Ok(Self {
+            spec: StackSpec::with_source(PxStackSpecType_t::PXStackFall, size, top as usize as u32),
+        })
+    }
+
+    /// Uses no separate stack, which the kernel does not check.
+    pub const fn dont_check() -> Self {
+        Self {
+            spec: StackSpec::dont_check(),
+        }
+    }
+
+    /// Returns the stack size in bytes.
+    pub const fn size(&self) -> u32 {
+        self.spec.size()
+    }
+
+    /// Returns the spec passed to the kernel.
+    pub const fn spec(&self) -> &StackSpec {
+        &self.spec
+    }
+}
+
 impl StackSpec {
-    /// Constructs a new default stack spec with size and memory.
-    pub fn new(size: u32, mem_class: PxMc_t) -> Self {
+    /// Constructs a stack spec allocating `size` bytes from the memory class.
+    ///
+    /// Fails if `size` is zero or not a multiple of [PxInt_t].
+    pub fn new(size: u32, mem_class: PxMc_t) -> PxResult<Self> {
+        Ok(Self::with_source(
+            PxStackSpecType_t::PXStackAlloc,
+            Self::words(size)?,
+            mem_class.as_raw(),
+        ))
+    }
+
+    /// Constructs a stack spec without a separate stack, which the kernel does not check.
+    pub const fn dont_check() -> Self {
+        Self::with_source(PxStackSpecType_t::PXStackDontCheck, 0, 0)
+    }
+
+    /// Returns the stack size in bytes.
+    pub const fn size(&self) -> u32 {
+        self.stk_size * core::mem::size_of::<PxInt_t>() as u32
+    }
+
+    /// Returns the bottom and the number of words of a painted pre-allocated stack.
+    #[cfg(feature = "stack-paint")]
+    pub(crate) fn painted(&self) -> Option<(*const u32, usize)> {
+        if self.stk_type != PxStackSpecType_t::PXStackFall {
+            return None;
+        }
+        let words = self.stk_size as usize;
+        let top = self.stk_src.bindgen_union_field as usize as *const u32;
+        Some((top.wrapping_sub(words), words))
+    }
+
+    /// Converts a size in bytes to [PxInt_t] words, rejecting sizes that would be truncated.
+    fn words(size: u32) -> PxResult<u32> {
+        let word = core::mem::size_of::<PxInt_t>() as u32;
+        if size == 0 || size & (word - 1) != 0 {
+            return Err(PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER);
+        }
+        Ok(size / word)
+    }
+
+    const fn with_source(stk_type: PxStackSpecType_t, words: u32, source: u32) -> Self {
         Self {
-            stk_type: PxStackSpecType_t::PXStackAlloc,
-            stk_size: size / core::mem::size_of::<PxInt_t>() as u32,
+            stk_type,
+            stk_size: words,
             stk_src: PxStackSpec_T__bindgen_ty_1 {
-                bindgen_union_field: mem_class.as_raw(),
-                mc: Default::default(),
-                stk: Default::default(),
+                bindgen_union_field: source,
+                mc: __BindgenUnionField::new(),
+                stk: __BindgenUnionField::new(),
             },
         }
     }
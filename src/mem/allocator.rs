@@ -0,0 +1,231 @@
+//! Allocators backed by PXROS memory classes [PxMc_t].
+//!
+//! Enabled with the `alloc` feature.
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: MemClassAllocator = MemClassAllocator::new(PxMc_t::SYSTEM);
+//! ```
+//!
+//! Blocks are taken from and returned to the memory class by the kernel, so
+//! the allocator can be used from several tasks as long as all of them
+//! resolve the handle to the same memory class. The symbolic
+//! [`PxMc_t::TASK`] resolves to the default memory class of the calling task,
+//! so a block freed by another task than the one that allocated it would be
+//! returned to the wrong class; it is rejected by both allocators.
+//!
+//! The nightly-only `allocator-api` feature adds [MemClass], an
+//! `Allocator` to place individual boxes and vectors in a specific memory
+//! class:
+//!
+//! ```ignore
+//! let state = MemClass::new(private_mc).try_box(State::default())?;
+//! let buffer: Vec<u8, _> = MemClass::new(shared_mc).try_vec(4096)?;
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::alloc::{GlobalAlloc, Layout};
+use core::ffi::c_void;
+use core::ptr::NonNull;
+
+use crate::bindings::*;
+use crate::PxResult;
+
+/// Alignment of the blocks returned by the kernel.
+const BLOCK_ALIGN: usize = 8;
+
+/// [GlobalAlloc] taking blocks from a memory class.
+///
+/// Alignments above the kernel block alignment are served by taking a larger
+/// block and storing its start right before the aligned pointer.
+pub struct MemClassAllocator {
+    mc: PxMc_t,
+}
+
+impl MemClassAllocator {
+    /// Creates an allocator for the memory class.
+    ///
+    /// # Panics
+    /// Panics if `mc` is the symbolic [`PxMc_t::TASK`], at compile time when
+    /// used to initialize a static.
+    pub const fn new(mc: PxMc_t) -> Self {
+        assert!(!is_task_symbolic(mc), "PxMc_t::TASK resolves per task");
+        Self { mc }
+    }
+
+    /// Returns the memory class allocations are taken from.
+    pub const fn memory_class(&self) -> PxMc_t {
+        self.mc
+    }
+}
+
+unsafe impl GlobalAlloc for MemClassAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        take(self.mc, layout).map_or(core::ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        give_back(self.mc, ptr, layout)
+    }
+}
+
+impl defmt::Format for MemClassAllocator {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "MemClassAllocator({})", self.mc)
+    }
+}
+
+/// Returns whether `mc` is the symbolic default memory class of the calling task.
+const fn is_task_symbolic(mc: PxMc_t) -> bool {
+    mc.as_raw() == PxMc_t::TASK.as_raw()
+}
+
+/// Takes a block of `size` bytes from the memory class.
+fn take_block(mc: PxMc_t, size: usize) -> PxResult<NonNull<u8>> {
+    let size = PxSize_t::try_from(size).map_err(|_| PxError_t::PXERR_MC_NOMEM)?;
+    let mut block: *mut c_void = core::ptr::null_mut();
+    // # Safety
+    // Parameters are copied and checked by PXROS, `block` is a valid out pointer.
+    let result: PxResult<()> = unsafe { PxMcTakeBlk(mc, &mut block, size) }.into();
+    result?;
+    NonNull::new(block.cast()).ok_or(PxError_t::PXERR_MC_NOMEM)
+}
+
+/// Takes a block for a non-zero sized `layout` from the memory class.
+fn take(mc: PxMc_t, layout: Layout) -> PxResult<NonNull<u8>> {
+    if layout.align() <= BLOCK_ALIGN {
+        return take_block(mc, layout.size());
+    }
+
+    let size = layout
+        .size()
+        .checked_add(layout.align())
+        .ok_or(PxError_t::PXERR_MC_NOMEM)?;
+    let block = take_block(mc, size)?.as_ptr();
+    // The offset is at least `BLOCK_ALIGN`, leaving room for the block start.
+    let offset = layout.align() - (block as usize & (layout.align() - 1));
+    // # Safety
+    // The block is `layout.align()` bytes larger than requested, so the
+    // aligned pointer and the stored block start lie within it.
+    unsafe {
+        let aligned = block.add(offset);
+        aligned.cast::<*mut u8>().sub(1).write(block);
+        Ok(NonNull::new_unchecked(aligned))
+    }
+}
+
+/// Returns a block taken with [take] to the memory class.
+///
+/// # Safety
+/// `ptr` must have been returned by [take] for `mc` and `layout`.
+unsafe fn give_back(mc: PxMc_t, ptr: *mut u8, layout: Layout) {
+    let block = if layout.align() <= BLOCK_ALIGN {
+        ptr
+    } else {
+        ptr.cast::<*mut u8>().sub(1).read()
+    };
+    // # Safety
+    // `block` was taken from this memory class by `take`.
+    let _ = PxMcReturnBlk(mc, block.cast());
+}
+
+#[cfg(feature = "allocator-api")]
+pub use allocator_api::MemClass;
+
+#[cfg(feature = "allocator-api")]
+mod allocator_api {
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use core::alloc::{AllocError, Allocator, Layout};
+    use core::ptr::NonNull;
+
+    use super::{give_back, is_task_symbolic, take};
+    use crate::bindings::*;
+    use crate::PxResult;
+
+    /// Memory class handle usable as [Allocator].
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct MemClass {
+        mc: PxMc_t,
+    }
+
+    impl MemClass {
+        /// Wraps the memory class.
+        ///
+        /// # Panics
+        /// Panics if `mc` is the symbolic [`PxMc_t::TASK`], as boxes and
+        /// vectors moved to another task would be freed to its memory class.
+        pub const fn new(mc: PxMc_t) -> Self {
+            assert!(!is_task_symbolic(mc), "PxMc_t::TASK resolves per task");
+            Self { mc }
+        }
+
+        /// Returns the raw memory class handle.
+        pub const fn handle(&self) -> PxMc_t {
+            self.mc
+        }
+
+        /// Allocates `value` in the memory class.
+        pub fn try_box<T>(self, value: T) -> PxResult<Box<T, Self>> {
+            let ptr = self.take(Layout::new::<T>())?.cast::<T>();
+            // # Safety
+            // The memory was allocated by `self` for a `T`.
+            unsafe {
+                ptr.as_ptr().write(value);
+                Ok(Box::from_raw_in(ptr.as_ptr(), self))
+            }
+        }
+
+        /// Creates an empty vector with room for `capacity` elements in the memory class.
+        pub fn try_vec<T>(self, capacity: usize) -> PxResult<Vec<T, Self>> {
+            let layout = Layout::array::<T>(capacity).map_err(|_| PxError_t::PXERR_MC_NOMEM)?;
+            let ptr = self.take(layout)?.cast::<T>();
+            let capacity = if core::mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                capacity
+            };
+            // # Safety
+            // The memory was allocated by `self` for `capacity` elements of `T`.
+            Ok(unsafe { Vec::from_raw_parts_in(ptr.as_ptr(), 0, capacity, self) })
+        }
+
+        /// Takes memory for `layout`, handing out a dangling pointer for zero sizes.
+        fn take(self, layout: Layout) -> PxResult<NonNull<u8>> {
+            if layout.size() == 0 {
+                // # Safety
+                // Alignments are never zero.
+                return Ok(unsafe { NonNull::new_unchecked(layout.align() as *mut u8) });
+            }
+            take(self.mc, layout)
+        }
+    }
+
+    impl From<PxMc_t> for MemClass {
+        fn from(mc: PxMc_t) -> Self {
+            Self::new(mc)
+        }
+    }
+
+    unsafe impl Allocator for MemClass {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let ptr = self.take(layout).map_err(|_| AllocError)?;
+            Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            if layout.size() != 0 {
+                give_back(self.mc, ptr.as_ptr(), layout)
+            }
+        }
+    }
+
+    impl defmt::Format for MemClass {
+        fn format(&self, fmt: defmt::Formatter) {
+            defmt::write!(fmt, "MemClass({})", self.mc)
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Memory classes [PxMc_t] created at runtime.
+//!
+//! A [MemoryClass] is a bounded heap built from static buffers. Giving each
+//! subsystem its own class keeps a runaway consumer from starving the others.
+//!
+//! ```ignore
+//! static mut CAN_HEAP: [u8; 4096] = [0; 4096];
+//!
+//! let can_mc = MemoryClass::variable()
+//!     .region(unsafe { &mut *core::ptr::addr_of_mut!(CAN_HEAP) })
+//!     .build()?;
+//! let frames = MessagePool::new(Frame::default(), can_mc.handle(), PxOpool_t::TASK)?;
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ffi::c_void;
+
+use crate::bindings::*;
+use crate::PxResult;
+
+/// Alignment of memory inserted into a class.
+const REGION_ALIGN: usize = 8;
+
+/// Runtime created memory class.
+///
+/// The class is never released, as blocks taken from it may outlive the handle.
+pub struct MemoryClass {
+    handle: PxMc_t,
+    mc_type: PxMcType_t,
+    capacity: usize,
+}
+
+impl MemoryClass {
+    /// Starts building a class handing out blocks of any size.
+    pub fn variable() -> MemoryClassBuilder {
+        MemoryClassBuilder::new(PxMcType_t::PXMcVarsized, 0)
+    }
+
+    /// Starts building a class handing out blocks of `block_size` bytes only.
+    pub fn fixed(block_size: usize) -> MemoryClassBuilder {
+        MemoryClassBuilder::new(PxMcType_t::PXMcFixsized, block_size)
+    }
+
+    /// Returns the raw memory class handle.
+    pub const fn handle(&self) -> PxMc_t {
+        self.handle
+    }
+
+    /// Returns the type of the class.
+    pub const fn mc_type(&self) -> PxMcType_t {
+        self.mc_type
+    }
+
+    /// Adds the buffer to the memory of the class.
+    ///
+    /// The start of the buffer is aligned up to 8 bytes.
+    pub fn insert(&mut self, region: &'static mut [u8]) -> PxResult<()> {
+        let offset = region.as_ptr().align_offset(REGION_ALIGN).min(region.len());
+        let region = &mut region[offset..];
+        let size = PxSize_t::try_from(region.len())
+            .map_err(|_| PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER)?;
+        // # Safety
+        // The region is `'static` and exclusively handed to the kernel.
+        let result: PxResult<()> =
+            unsafe { PxMcInsert(self.handle, region.as_mut_ptr() as *mut c_void, size) }.into();
+        result?;
+        self.capacity += region.len();
+        Ok(())
+    }
+
+    /// Returns the number of bytes inserted into the class.
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the current free and used memory of the class.
+    pub fn stats(&self) -> PxResult<MemoryStats> {
+        let mut free: PxSize_t = 0;
+        // # Safety
+        // Parameters are copied and checked by PXROS, `free` is a valid out pointer.
+        let result: PxResult<()> = unsafe { PxMcGetSize(self.handle, &mut free) }.into();
+        result?;
+        let free = free as usize;
+        Ok(MemoryStats {
+            capacity: self.capacity,
+            free,
+            used: self.capacity.saturating_sub(free),
+        })
+    }
+}
+
+impl From<&MemoryClass> for PxMc_t {
+    fn from(class: &MemoryClass) -> Self {
+        class.handle
+    }
+}
+
+impl defmt::Format for MemoryClass {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "MemoryClass({}, capacity: {})",
+            self.handle,
+            self.capacity
+        )
+    }
+}
+
+/// Builder for a [MemoryClass].
+#[must_use = "The memory class is only created by `build`"]
+pub struct MemoryClassBuilder {
+    mc_type: PxMcType_t,
+    block_size: usize,
+    opool: PxOpool_t,
+    region: Option<&'static mut [u8]>,
+}
+
+impl MemoryClassBuilder {
+    fn new(mc_type: PxMcType_t, block_size: usize) -> Self {
+        Self {
+            mc_type,
+            block_size,
+            opool: PxOpool_t::default(),
+            region: None,
+        }
+    }
+
+    /// Requests the class object from `opool` instead of the default task object pool.
+    pub fn opool(mut self, opool: impl Into<PxOpool_t>) -> Self {
+        self.opool = opool.into();
+        self
+    }
+
+    /// Sets the initial memory of the class, more can be added with [`MemoryClass::insert`].
+    pub fn region(mut self, region: &'static mut [u8]) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Creates the memory class.
+    pub fn build(self) -> PxResult<MemoryClass> {
+        let block_size = PxSize_t::try_from(self.block_size)
+            .map_err(|_| PxError_t::PXERR_REQUEST_ILLEGAL_PARAMETER)?;
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        let handle = unsafe { PxMcRequest(self.opool, self.mc_type, block_size) }.checked()?;
+        let mut class = MemoryClass {
+            handle,
+            mc_type: self.mc_type,
+            capacity: 0,
+        };
+        if let Some(region) = self.region {
+            class.insert(region)?;
+        }
+        Ok(class)
+    }
+}
+
+/// Memory statistics of a [MemoryClass] in bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct MemoryStats {
+    /// Memory inserted into the class.
+    pub capacity: usize,
+    /// Memory available for new blocks.
+    pub free: usize,
+    /// Memory taken by blocks and kernel bookkeeping.
+    pub used: usize,
+}
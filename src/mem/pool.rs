@@ -0,0 +1,97 @@
+//! Object pools [PxOpool_t] created at runtime.
+//!
+//! Every kernel object (mailboxes, messages, delays, ...) is taken from an
+//! object pool. An [ObjectPool] bounds the number of objects a subsystem can
+//! hold and makes its usage observable:
+//!
+//! ```ignore
+//! let can_pool = ObjectPool::request(PxOpoolType_t::PXOpoolReal, 16, PxOpool_t::TASK)?;
+//! let mailbox = Mailbox::request(&can_pool)?;
+//! defmt::info!("CAN objects left: {}", can_pool.free());
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::bindings::*;
+use crate::PxResult;
+
+impl PxOpool_t {
+    /// Returns the number of objects currently available in the pool.
+    pub fn free(&self) -> u32 {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxOpoolGetCurrentCapacity(*self) }
+    }
+}
+
+/// Runtime created object pool.
+///
+/// A [PxOpoolType_t::PXOpoolReal] pool takes its objects from the source pool
+/// on creation, a [PxOpoolType_t::PXOpoolVirtual] pool only takes them once
+/// they are requested, bounded by its capacity.
+///
+/// The pool is never released, as objects taken from it may outlive the handle.
+pub struct ObjectPool {
+    handle: PxOpool_t,
+    opool_type: PxOpoolType_t,
+}
+
+impl ObjectPool {
+    /// Requests a pool of `capacity` objects taken from `source`.
+    ///
+    /// The pool object itself is requested from `source` as well.
+    pub fn request(
+        opool_type: PxOpoolType_t,
+        capacity: u32,
+        source: impl Into<PxOpool_t>,
+    ) -> PxResult<Self> {
+        let source = source.into();
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        let handle = unsafe { PxOpoolRequest(source, opool_type, capacity, source) }.checked()?;
+        Ok(Self { handle, opool_type })
+    }
+
+    /// Returns the raw object pool handle.
+    pub const fn handle(&self) -> PxOpool_t {
+        self.handle
+    }
+
+    /// Returns the type of the pool.
+    pub const fn opool_type(&self) -> PxOpoolType_t {
+        self.opool_type
+    }
+
+    /// Returns the number of objects currently available in the pool.
+    pub fn free(&self) -> u32 {
+        self.handle.free()
+    }
+
+    /// Moves `count` objects from `source` into this pool.
+    pub fn insert_from(&self, source: impl Into<PxOpool_t>, count: u32) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxOpoolInsert(self.handle, source.into(), count) }.into()
+    }
+
+    /// Moves `count` objects from this pool into `destination`.
+    pub fn move_to(&self, destination: impl Into<PxOpool_t>, count: u32) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxOpoolInsert(destination.into(), self.handle, count) }.into()
+    }
+}
+
+impl From<&ObjectPool> for PxOpool_t {
+    fn from(pool: &ObjectPool) -> Self {
+        pool.handle
+    }
+}
+
+impl defmt::Format for ObjectPool {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "ObjectPool({}, free: {})", self.handle, self.free())
+    }
+}
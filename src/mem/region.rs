@@ -0,0 +1,192 @@
+//! Validated sets of memory protection regions.
+//!
+//! PXROS expects the protection regions of a task as an array terminated by a
+//! zeroed [MemoryRegion]. A [RegionSet] adds the terminator itself and checks
+//! the regions against the TriCore MPU rules:
+//!
+//! * ranges must not be empty or inverted,
+//! * bounds must be aligned to the 8 byte MPU granularity,
+//! * regions must not overlap.
+//!
+//! Sets built with [`RegionSet::new`] in a `const` or `static` are checked at
+//! compile time:
+//!
+//! ```ignore
+//! static REGIONS: RegionSet<2> = RegionSet::new([
+//!     MemoryRegion::new(0x7000_0000..0x7000_1000, PxProtectType_t::WRProtection),
+//!     MemoryRegion::new(0xF000_0000..0xF000_0100, PxProtectType_t::ReadProtection),
+//! ]);
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use super::MemoryRegion;
+use crate::bindings::*;
+
+/// Granularity of the TriCore MPU range bounds in bytes.
+///
+/// `pxros_build::REGION_GRANULARITY` aligns the linker sections to the same
+/// value.
+pub const REGION_GRANULARITY: u32 = 8;
+
+/// Reason a [RegionSet] was rejected, with the index of the offending regions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RegionError {
+    /// The region covers no memory, it would terminate the set early.
+    Empty(usize),
+    /// The lower bound lies above the upper bound.
+    Inverted(usize),
+    /// A bound is not aligned to [REGION_GRANULARITY].
+    Misaligned(usize),
+    /// Two regions cover common memory.
+    Overlapping(usize, usize),
+}
+
+impl RegionError {
+    /// Returns a description of the error.
+    pub const fn message(&self) -> &'static str {
+        match self {
+            RegionError::Empty(_) => "protection region is empty",
+            RegionError::Inverted(_) => "protection region is inverted",
+            RegionError::Misaligned(_) => "protection region is not aligned to 8 bytes",
+            RegionError::Overlapping(_, _) => "protection regions overlap",
+        }
+    }
+}
+
+/// Zero-terminated set of `N` validated protection regions.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RegionSet<const N: usize> {
+    regions: [MemoryRegion; N],
+    terminator: MemoryRegion,
+}
+
+impl<const N: usize> RegionSet<N> {
+    /// Creates the set, panicking if the regions are invalid.
+    ///
+    /// In a `const` context the panic becomes a compile error.
+    pub const fn new(regions: [MemoryRegion; N]) -> Self {
+        match Self::try_new(regions) {
+            Ok(set) => set,
+            Err(error) => panic!("{}", error.message()),
+        }
+    }
+
+    /// Creates the set, returning the first rule the regions violate.
+    pub const fn try_new(regions: [MemoryRegion; N]) -> Result<Self, RegionError> {
+        if let Err(error) = validate(&regions) {
+            return Err(error);
+        }
+        Ok(Self {
+            regions,
+            terminator: MemoryRegion::zeroed(),
+        })
+    }
+
+    /// Returns the regions without the terminator.
+    pub const fn regions(&self) -> &[MemoryRegion; N] {
+        &self.regions
+    }
+
+    /// Returns the zero-terminated array as expected by the task spec.
+    pub const fn as_ptr(&self) -> *const PxProtectRegion_T {
+        // `repr(C)` places the terminator right after the regions.
+        self as *const Self as *const PxProtectRegion_T
+    }
+}
+
+const fn validate<const N: usize>(regions: &[MemoryRegion; N]) -> Result<(), RegionError> {
+    let mut index = 0;
+    while index < N {
+        let region = &regions[index];
+        if region.lowerBound > region.upperBound {
+            return Err(RegionError::Inverted(index));
+        }
+        if region.lowerBound == region.upperBound {
+            return Err(RegionError::Empty(index));
+        }
+        if (region.lowerBound | region.upperBound) & (REGION_GRANULARITY - 1) != 0 {
+            return Err(RegionError::Misaligned(index));
+        }
+        let mut other = 0;
+        while other < index {
+            let previous = &regions[other];
+            if region.lowerBound < previous.upperBound && previous.lowerBound < region.upperBound {
+                return Err(RegionError::Overlapping(other, index));
+            }
+            other += 1;
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+impl<const N: usize> defmt::Format for RegionSet<N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "RegionSet(");
+        for (index, region) in self.regions.iter().enumerate() {
+            if index > 0 {
+                defmt::write!(fmt, ", ");
+            }
+            defmt::write!(
+                fmt,
+                "{=u32:#x}..{=u32:#x}",
+                region.lowerBound,
+                region.upperBound
+            );
+        }
+        defmt::write!(fmt, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(start: u32, end: u32) -> MemoryRegion {
+        MemoryRegion::new(start..end, PxProtectType_t::WRProtection)
+    }
+
+    #[test]
+    fn accepts_disjoint_aligned_regions() {
+        let set = RegionSet::try_new([region(0x100, 0x200), region(0x200, 0x208)]).unwrap();
+        assert_eq!(set.regions()[1].upperBound, 0x208);
+        assert_eq!(set.terminator.upperBound, 0);
+    }
+
+    #[test]
+    fn rejects_overlapping_regions() {
+        let result = RegionSet::try_new([
+            region(0x100, 0x200),
+            region(0x400, 0x500),
+            region(0x1F8, 0x300),
+        ]);
+        assert_eq!(result.err(), Some(RegionError::Overlapping(0, 2)));
+        let contained = RegionSet::try_new([region(0x100, 0x400), region(0x200, 0x300)]);
+        assert_eq!(contained.err(), Some(RegionError::Overlapping(0, 1)));
+    }
+
+    #[test]
+    fn rejects_misaligned_bounds() {
+        let lower = RegionSet::try_new([region(0x104, 0x200)]);
+        assert_eq!(lower.err(), Some(RegionError::Misaligned(0)));
+        let upper = RegionSet::try_new([region(0x100, 0x200), region(0x300, 0x301)]);
+        assert_eq!(upper.err(), Some(RegionError::Misaligned(1)));
+    }
+
+    #[test]
+    fn rejects_empty_and_inverted_regions() {
+        let empty = RegionSet::try_new([region(0x100, 0x100)]);
+        assert_eq!(empty.err(), Some(RegionError::Empty(0)));
+        let inverted = RegionSet::try_new([region(0x100, 0x200), region(0x300, 0x200)]);
+        assert_eq!(inverted.err(), Some(RegionError::Inverted(1)));
+    }
+
+    #[test]
+    fn matches_linker_fragment_granularity() {
+        assert_eq!(REGION_GRANULARITY, pxros_build::REGION_GRANULARITY);
+    }
+}
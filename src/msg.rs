@@ -0,0 +1,256 @@
+//! Typed wrappers around [PxMbx_t] and [PxMsg_t].
+//!
+//! A [Mailbox] is a plain kernel handle, a [Message] owns its kernel message
+//! and releases it on drop. Received messages are [ReadOnly] until checked
+//! with [`Message::writable`], see [protection].
+//!
+//! Message buffers are shared with other tasks, so messages only carry
+//! [Plain] data that is valid for any bit pattern a peer may write.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::marker::PhantomData;
+
+use crate::bindings::*;
+use crate::PxResult;
+
+pub mod await_release;
+pub mod channel;
+pub mod metadata;
+pub(crate) mod notify;
+pub mod pool;
+pub mod protection;
+pub mod recv;
+
+pub use await_release::{AwaitRelease, PendingRelease};
+pub use channel::{channel, channel_in, Receiver, Sender, TrySendError};
+pub use metadata::{FieldLayout, Metadata, MetadataError, MetadataField};
+pub use pool::{MessagePool, PoolMessage};
+pub use protection::{Access, ReadOnly, ReadWrite};
+pub use recv::Recv;
+
+/// Plain data that is valid for any bit pattern.
+///
+/// Messages read their data in place from buffers written by other tasks, so
+/// their data type must not have invalid values. References, `bool`, `char`
+/// and enums are excluded for this reason.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type must be a valid value. For
+/// structs this holds if all fields are `Plain`, use `#[repr(C)]` to share
+/// them with C tasks.
+pub unsafe trait Plain: Copy {}
+
+macro_rules! impl_plain {
+    ($($ty:ty),*) => {
+        $(
+            // # Safety
+            // Integers and floats are valid for any bit pattern.
+            unsafe impl Plain for $ty {}
+        )*
+    };
+}
+
+impl_plain!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+// # Safety
+// Arrays of plain data have no other invariants than their elements.
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+// # Safety
+// The unit type has no bits.
+unsafe impl Plain for () {}
+
+/// Type export for working with [PxMbx_t]
+pub type Mailbox = PxMbx_t;
+
+impl Mailbox {
+    /// Requests a new mailbox from the object pool.
+    pub fn request(opool: impl Into<PxOpool_t>) -> PxResult<Self> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxMbxRequest(opool.into()) }.checked()
+    }
+
+    /// Returns the private mailbox of a task.
+    pub fn of_task(task: PxTask_t) -> PxResult<Self> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxTaskGetMbx(task) }.checked()
+    }
+
+    /// Releases the mailbox back to its object pool.
+    pub fn release(self) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxMbxRelease(self) }.checked().map(|_| ())
+    }
+
+    /// Receives the next message, blocking until one is available.
+    pub fn receive<T: Plain>(&self) -> PxResult<Message<T, ReadOnly>> {
+        // # Safety
+        // A received message is owned by the receiver only.
+        unsafe { Message::from_handle(PxMsgReceive(*self)) }
+    }
+
+    /// Receives the next message if one is available.
+    pub fn try_receive<T: Plain>(&self) -> PxResult<Option<Message<T, ReadOnly>>> {
+        let handle = PxMsgReceive_NoWait(*self).checked()?;
+        if handle.is_valid() {
+            // # Safety
+            // A received message is owned by the receiver only.
+            unsafe { Message::from_handle(handle) }.map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Owned kernel message carrying a `T`.
+///
+/// `T` is read and written in place in the message buffer, so it must be
+/// [Plain] data that stays valid for any bit pattern a peer task may write.
+///
+/// Dropping the message releases it, which hands it to its release mailbox if
+/// one was installed.
+///
+/// `A` is the [Access] the handle grants, messages are created [ReadWrite].
+#[must_use = "Dropping a message releases it"]
+pub struct Message<T, A = ReadWrite> {
+    handle: PxMsg_t,
+    marker: PhantomData<(T, A)>,
+}
+
+impl<T: Plain> Message<T> {
+    /// Requests a new message from the memory class and initializes it with `value`.
+    pub fn new(value: T, mc: PxMc_t, opool: impl Into<PxOpool_t>) -> PxResult<Self> {
+        let handle =
+            PxMsgRequest(core::mem::size_of::<T>() as PxSize_t, mc, opool.into()).checked()?;
+        // # Safety
+        // The message was just requested and is owned by nobody else.
+        let mut message = unsafe { Self::from_handle(handle) }?;
+        *message.data_mut() = value;
+        Ok(message)
+    }
+
+    /// Returns the message data mutably.
+    pub fn data_mut(&mut self) -> &mut T {
+        // # Safety
+        // See `data`, the exclusive borrow of `self` guarantees unique access
+        // and the buffer is not write protected.
+        unsafe { &mut *self.data_ptr() }
+    }
+}
+
+impl<T: Plain, A: Access> Message<T, A> {
+    /// Takes ownership of a raw message handle.
+    ///
+    /// Checks that the message buffer is large enough and suitably aligned for
+    /// `T`, and that its protection permits the access `A`. Mismatching
+    /// messages are released.
+    ///
+    /// # Safety
+    /// The caller transfers unique ownership of the handle: no other
+    /// [Message] or raw use of the handle may exist afterwards.
+    pub unsafe fn from_handle(handle: PxMsg_t) -> PxResult<Self> {
+        let message = Self {
+            handle: handle.checked()?,
+            marker: PhantomData,
+        };
+        let data = message.data_ptr();
+        if data.is_null() {
+            return Err(PxError_t::PXERR_ILL_NULLPOINTER_PARAMETER);
+        }
+        if (PxMsgGetSize(handle) as usize) < core::mem::size_of::<T>()
+            || data.align_offset(core::mem::align_of::<T>()) != 0
+        {
+            return Err(PxError_t::PXERR_MSG_ILLSIZE);
+        }
+        if !A::permits(PxMsgGetProtection(handle)) {
+            return Err(PxError_t::PXERR_ILLEGAL_ACCESS);
+        }
+        Ok(message)
+    }
+
+    /// Returns the message data.
+    pub fn data(&self) -> &T {
+        // # Safety
+        // Size and alignment were checked on construction, the buffer is owned
+        // by this message until it is sent or released.
+        unsafe { &*self.data_ptr() }
+    }
+
+    /// Sends the message to a mailbox.
+    ///
+    /// Returns the error and the message if the kernel refused it.
+    pub fn send(self, mailbox: Mailbox) -> Result<(), (PxError_t, Self)> {
+        match PxMsgSend(self.handle, mailbox).checked() {
+            Ok(_) => {
+                core::mem::forget(self);
+                Ok(())
+            }
+            Err(error) => Err((error, self)),
+        }
+    }
+
+    /// Sends the message to a mailbox, ahead of normal priority messages.
+    pub fn send_prio(self, mailbox: Mailbox) -> Result<(), (PxError_t, Self)> {
+        match PxMsgSend_Prio(self.handle, mailbox).checked() {
+            Ok(_) => {
+                core::mem::forget(self);
+                Ok(())
+            }
+            Err(error) => Err((error, self)),
+        }
+    }
+
+    fn data_ptr(&self) -> *mut T {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxMsgGetData(self.handle) }.0 as *mut T
+    }
+
+    /// Changes the access type, the caller ensures the protection permits it.
+    fn cast<B: Access>(self) -> Message<T, B> {
+        Message {
+            handle: self.into_handle(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A> Message<T, A> {
+    /// Returns the raw message handle.
+    pub const fn handle(&self) -> PxMsg_t {
+        self.handle
+    }
+
+    /// Installs the mailbox this message is handed to when it is released.
+    pub fn install_release_mailbox(&self, mailbox: Mailbox) -> PxResult<()> {
+        PxMsgInstallRelmbx(self.handle, mailbox).into()
+    }
+
+    /// Gives up ownership and returns the raw message handle.
+    pub fn into_handle(self) -> PxMsg_t {
+        let handle = self.handle;
+        core::mem::forget(self);
+        handle
+    }
+}
+
+impl<T, A> Drop for Message<T, A> {
+    fn drop(&mut self) {
+        // # Safety
+        // The handle is owned by this message and not used afterwards.
+        let _ = unsafe { PxMsgRelease(self.handle) };
+    }
+}
+
+impl<T, A> defmt::Format for Message<T, A> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Message({})", self.handle)
+    }
+}
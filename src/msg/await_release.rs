@@ -0,0 +1,256 @@
+//! Sending messages that are handed back to the sender once released.
+//!
+//! [PxMsgSetToAwaitRel] marks a message so that releasing it on the receiving
+//! side does not free it, but wakes the sender blocked in [PxMsgAwaitRel]. The
+//! sender owns the buffer again afterwards and may reuse it.
+//!
+//! [`Message::send_await_release`] consumes the message and returns a
+//! [PendingRelease] guard. The message can only be recovered by awaiting the
+//! guard, so the sender cannot touch the buffer while the receiver uses it.
+//!
+//! The kernel only wakes a sender blocked in [PxMsgAwaitRel] itself, so an
+//! [Executor](crate::executor::Executor) cannot wait for several releases at
+//! once this way. [`Message::send_released`] requests a release mailbox of
+//! its own instead, which the message is handed to when the receiver releases
+//! it. The returned [AwaitRelease] future waits for it there like
+//! [`Mailbox::recv`] does.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::recv::MailboxWait;
+#[cfg(doc)]
+use super::PoolMessage;
+use super::{Access, Mailbox, Message, Plain, ReadWrite};
+use crate::bindings::*;
+use crate::PxResult;
+
+impl<T: Plain, A: Access> Message<T, A> {
+    /// Sends the message to a mailbox and marks it to be handed back once released.
+    ///
+    /// Returns the error and the message if the kernel refused it.
+    pub fn send_await_release(
+        self,
+        mailbox: Mailbox,
+    ) -> Result<PendingRelease<T, A>, (PxError_t, Self)> {
+        if let Err(error) = PxResult::from(PxMsgSetToAwaitRel(self.handle())) {
+            return Err((error, self));
+        }
+        let handle = self.handle();
+        self.send(mailbox)?;
+        Ok(PendingRelease {
+            handle,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// Guard for a message that was sent with [`Message::send_await_release`].
+///
+/// Dropping the guard blocks until the receiver released the message and
+/// releases it for good. The message is handed back with the access `A` it
+/// was sent with.
+#[must_use = "Dropping a pending release blocks until the receiver released the message"]
+pub struct PendingRelease<T: Plain, A: Access = ReadWrite> {
+    handle: PxMsg_t,
+    marker: PhantomData<(T, A)>,
+}
+
+impl<T: Plain, A: Access> PendingRelease<T, A> {
+    /// Returns the raw message handle.
+    pub const fn handle(&self) -> PxMsg_t {
+        self.handle
+    }
+
+    /// Blocks until the receiver released the message and returns it.
+    pub fn wait(self) -> PxResult<Message<T, A>> {
+        let handle = PxMsgAwaitRel(self.handle).checked()?;
+        Ok(self.into_message(handle))
+    }
+
+    /// Returns the message if the receiver already released it.
+    ///
+    /// Kernel errors are treated like a message that was not released yet,
+    /// [`PendingRelease::wait`] reports them.
+    pub fn try_wait(self) -> Result<Message<T, A>, Self> {
+        match PxMsgAwaitRel_NoWait(self.handle).checked() {
+            Ok(handle) if handle.is_valid() => Ok(self.into_message(handle)),
+            _ => Err(self),
+        }
+    }
+
+    /// Blocks until the receiver released the message or one of `events` arrives.
+    ///
+    /// Returns the received events and the guard if the events arrived first.
+    pub fn wait_events(self, events: PxEvents_t) -> Result<Message<T, A>, (PxEvents_t, Self)> {
+        let result = PxMsgAwaitRel_EvWait(self.handle, events);
+        if result.msg.is_valid() {
+            Ok(self.into_message(result.msg))
+        } else {
+            Err((result.events, self))
+        }
+    }
+
+    /// Hands ownership of the released message back to the sender.
+    ///
+    /// The buffer was checked for `T` when the message was first constructed.
+    fn into_message(self, handle: PxMsg_t) -> Message<T, A> {
+        core::mem::forget(self);
+        Message {
+            handle,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Plain, A: Access> Drop for PendingRelease<T, A> {
+    fn drop(&mut self) {
+        let handle = PxMsgAwaitRel(self.handle);
+        if handle.is_valid() {
+            // # Safety
+            // The receiver released the message, ownership is back with us.
+            let _ = unsafe { PxMsgRelease(handle) };
+        }
+    }
+}
+
+impl<T: Plain, A: Access> defmt::Format for PendingRelease<T, A> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "PendingRelease({})", self.handle)
+    }
+}
+
+/// Future returned by [`Message::send_released`].
+///
+/// Dropping the future before completion blocks until the receiver released
+/// the message and releases it for good, like dropping a [PendingRelease].
+#[must_use = "futures do nothing unless polled"]
+pub struct AwaitRelease<T: Plain, A: Access = ReadWrite> {
+    wait: MailboxWait,
+    handle: PxMsg_t,
+    released: bool,
+    marker: PhantomData<(T, A)>,
+}
+
+impl<T: Plain, A: Access> Message<T, A> {
+    /// Sends the message to a mailbox and returns a future that completes once it was released.
+    ///
+    /// A release mailbox only used by the future is requested from `opool`
+    /// and installed until the future completes. Messages of a
+    /// [MessagePool](super::MessagePool) are [PoolMessage]s, whose release
+    /// mailbox belongs to the pool, and cannot be sent this way.
+    ///
+    /// Returns the error and the message if the kernel refused it.
+    pub fn send_released(
+        self,
+        mailbox: Mailbox,
+        opool: impl Into<PxOpool_t>,
+    ) -> Result<AwaitRelease<T, A>, (PxError_t, Self)> {
+        let release = match Mailbox::request(opool) {
+            Ok(release) => release,
+            Err(error) => return Err((error, self)),
+        };
+        if let Err(error) = self.install_release_mailbox(release) {
+            let _ = release.release();
+            return Err((error, self));
+        }
+        let handle = self.handle();
+        if let Err((error, message)) = self.send(mailbox) {
+            let _ = PxMsgInstallRelmbx(handle, Mailbox::invalid());
+            let _ = release.release();
+            return Err((error, message));
+        }
+        Ok(AwaitRelease {
+            wait: MailboxWait::new(release),
+            handle,
+            released: false,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T: Plain, A: Access> AwaitRelease<T, A> {
+    /// Returns the raw message handle.
+    pub const fn handle(&self) -> PxMsg_t {
+        self.handle
+    }
+
+    /// Takes the message back from the release mailbox and releases the mailbox.
+    ///
+    /// An invalid mailbox removes the release mailbox, so releasing the
+    /// message afterwards frees it.
+    fn take_back(&mut self) -> Message<T, A> {
+        self.released = true;
+        let _ = PxMsgInstallRelmbx(self.handle, Mailbox::invalid());
+        let _ = self.wait.mailbox().release();
+        Message {
+            handle: self.handle,
+            marker: PhantomData,
+        }
+    }
+}
+
+// The message is only referenced by its handle.
+impl<T: Plain, A: Access> Unpin for AwaitRelease<T, A> {}
+
+impl<T: Plain, A: Access> Future for AwaitRelease<T, A> {
+    type Output = PxResult<Message<T, A>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        assert!(!this.released, "AwaitRelease polled after completion");
+        loop {
+            match this.wait.poll_receive(cx) {
+                Poll::Ready(Ok(handle)) if handle == this.handle => {
+                    return Poll::Ready(Ok(this.take_back()));
+                }
+                Poll::Ready(Ok(handle)) => release_foreign(handle),
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T: Plain, A: Access> Drop for AwaitRelease<T, A> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        self.wait.finish();
+        let release = self.wait.mailbox();
+        loop {
+            match PxMsgReceive(release).checked() {
+                Ok(handle) if handle == self.handle => break,
+                Ok(handle) => release_foreign(handle),
+                // The message cannot be recovered, leave it to its release mailbox.
+                Err(_) => return,
+            }
+        }
+        drop(self.take_back());
+    }
+}
+
+/// Releases a message that was sent to a release mailbox it does not belong to.
+fn release_foreign(handle: PxMsg_t) {
+    // # Safety
+    // The message was received from the mailbox and is owned by us.
+    let _ = unsafe { PxMsgRelease(handle) };
+}
+
+impl<T: Plain, A: Access> defmt::Format for AwaitRelease<T, A> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "AwaitRelease({}, released: {})",
+            self.handle,
+            self.released
+        )
+    }
+}
@@ -0,0 +1,172 @@
+//! One-way typed channels over a mailbox and a [MessagePool].
+//!
+//! [channel] creates a mailbox and returns a cloneable [Sender] and a single
+//! [Receiver] for it. Values travel in messages taken from the pool, so the
+//! pool size bounds the number of values in flight: [`Sender::send`] blocks
+//! and [`Sender::try_send`] fails while all messages are taken. Received
+//! messages return to the pool as soon as their value is read.
+//!
+//! ```ignore
+//! static POOL: StaticCell<MessagePool<Sample, 8>> = ...;
+//! let (tx, rx) = channel(POOL.get())?;
+//!
+//! // Producer task
+//! tx.send(sample)?;
+//!
+//! // Consumer task
+//! while let Some(sample) = rx.recv_timeout(&timeout)? { ... }
+//! ```
+//!
+//! Like the pool, the channel mailbox is never released.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::marker::PhantomData;
+
+use super::{Mailbox, Message, MessagePool, Plain, PoolMessage, ReadOnly};
+use crate::bindings::*;
+use crate::delay::Timeout;
+use crate::PxResult;
+
+/// Creates a channel carrying values in messages of `pool`.
+///
+/// The mailbox is requested from the default task object pool.
+pub fn channel<T: Plain, const N: usize>(
+    pool: &MessagePool<T, N>,
+) -> PxResult<(Sender<'_, T, N>, Receiver<'_, T, N>)> {
+    channel_in(pool, PxOpool_t::default())
+}
+
+/// Creates a channel carrying values in messages of `pool`, requesting the mailbox from `opool`.
+pub fn channel_in<T: Plain, const N: usize>(
+    pool: &MessagePool<T, N>,
+    opool: impl Into<PxOpool_t>,
+) -> PxResult<(Sender<'_, T, N>, Receiver<'_, T, N>)> {
+    let mailbox = Mailbox::request(opool)?;
+    Ok((
+        Sender { pool, mailbox },
+        Receiver {
+            mailbox,
+            marker: PhantomData,
+        },
+    ))
+}
+
+/// Error of [`Sender::try_send`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// All messages of the pool are in flight, the value is returned.
+    Full(T),
+    /// A kernel call failed.
+    Kernel(PxError_t),
+}
+
+impl<T> From<PxError_t> for TrySendError<T> {
+    fn from(error: PxError_t) -> Self {
+        TrySendError::Kernel(error)
+    }
+}
+
+impl<T> defmt::Format for TrySendError<T> {
+    fn format(&self, fmt: defmt::Formatter) {
+        match self {
+            TrySendError::Full(_) => defmt::write!(fmt, "Full"),
+            TrySendError::Kernel(error) => defmt::write!(fmt, "Kernel({})", error),
+        }
+    }
+}
+
+/// Sending half of a [channel].
+pub struct Sender<'p, T, const N: usize> {
+    pool: &'p MessagePool<T, N>,
+    mailbox: Mailbox,
+}
+
+impl<T: Plain, const N: usize> Sender<'_, T, N> {
+    /// Sends a value, blocking until a message of the pool is available.
+    pub fn send(&self, value: T) -> PxResult<()> {
+        self.forward(self.pool.take(value)?)
+    }
+
+    /// Sends a value if a message of the pool is available.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self.pool.try_take(value)? {
+            Some(message) => Ok(self.forward(message)?),
+            None => Err(TrySendError::Full(value)),
+        }
+    }
+
+    /// Sends a taken message, which returns to the pool if the kernel refuses it.
+    fn forward(&self, message: PoolMessage<T>) -> PxResult<()> {
+        message.send(self.mailbox).map_err(|(error, _)| error)
+    }
+}
+
+impl<T, const N: usize> Clone for Sender<'_, T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool,
+            mailbox: self.mailbox,
+        }
+    }
+}
+
+impl<T, const N: usize> defmt::Format for Sender<'_, T, N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Sender({})", self.mailbox)
+    }
+}
+
+/// Receiving half of a [channel].
+pub struct Receiver<'p, T, const N: usize> {
+    mailbox: Mailbox,
+    marker: PhantomData<&'p MessagePool<T, N>>,
+}
+
+impl<T: Plain, const N: usize> Receiver<'_, T, N> {
+    /// Receives the next value, blocking until one is available.
+    pub fn recv(&self) -> PxResult<T> {
+        self.mailbox.receive().map(Self::read)
+    }
+
+    /// Receives the next value if one is available.
+    pub fn try_recv(&self) -> PxResult<Option<T>> {
+        Ok(self.mailbox.try_receive()?.map(Self::read))
+    }
+
+    /// Receives the next value, blocking until one is available or `timeout` elapsed.
+    ///
+    /// Returns `None` on timeout. The timeout is restarted for the call and
+    /// stopped afterwards.
+    pub fn recv_timeout(&self, timeout: &Timeout) -> PxResult<Option<T>> {
+        timeout.events().reset();
+        timeout.start()?;
+        let result = PxMsgReceive_EvWait(self.mailbox, timeout.events());
+        let _ = timeout.stop();
+        timeout.events().reset();
+        if !result.msg.is_valid() {
+            return Ok(None);
+        }
+        // # Safety
+        // A received message is owned by the receiver only.
+        unsafe { Message::from_handle(result.msg) }.map(|message| Some(Self::read(message)))
+    }
+
+    /// Returns the mailbox of the channel, e.g. to wait on it with [Select](crate::select::Select).
+    pub const fn mailbox(&self) -> Mailbox {
+        self.mailbox
+    }
+
+    /// Reads the value, the message returns to the pool on drop.
+    fn read(message: Message<T, ReadOnly>) -> T {
+        *message.data()
+    }
+}
+
+impl<T, const N: usize> defmt::Format for Receiver<'_, T, N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Receiver({}, capacity: {})", self.mailbox, N)
+    }
+}
@@ -0,0 +1,382 @@
+//! Structured message metadata over [PxMsgGetMetadata] and [PxMsgSetMetadata].
+//!
+//! Every message carries a 64-bit [PxMsgMetadata_t]. [message_metadata!](crate::message_metadata)
+//! defines a [Metadata] layout packing fields of given bit widths, starting at
+//! the least significant bit:
+//!
+//! ```ignore
+//! pxros::message_metadata! {
+//!     /// Header of all gateway messages.
+//!     pub struct Header {
+//!         #[bits(8)]
+//!         pub kind: u8,
+//!         #[bits(4)]
+//!         pub version: u8,
+//!         #[bits(32)]
+//!         pub seq: u32,
+//!     }
+//! }
+//!
+//! message.set_metadata(&Header { kind: 1, version: 2, seq })?;
+//! let header: Header = message.metadata()?;
+//! ```
+//!
+//! Field types implement [MetadataField], which user types such as enums can
+//! implement to reject invalid values on decoding.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use super::Message;
+use crate::bindings::*;
+use crate::PxResult;
+
+/// Layout of the 64-bit message metadata.
+pub trait Metadata: Sized {
+    /// Number of bits used by the layout.
+    const BITS: u32;
+
+    /// Fields from the least significant bit on.
+    const FIELDS: &'static [FieldLayout];
+
+    /// Packs the fields, failing if a value does not fit its width.
+    fn encode(&self) -> Result<u64, MetadataError>;
+
+    /// Unpacks the fields, failing on unused bits or invalid field values.
+    fn decode(bits: u64) -> Result<Self, MetadataError>;
+}
+
+/// Position of a field in a [Metadata] layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct FieldLayout {
+    /// Field name.
+    pub name: &'static str,
+    /// Position of the least significant bit of the field.
+    pub offset: u32,
+    /// Width of the field in bits.
+    pub width: u32,
+}
+
+impl FieldLayout {
+    /// Returns the mask of the unshifted field value.
+    pub const fn mask(&self) -> u64 {
+        mask(self.width)
+    }
+}
+
+/// Field of a [Metadata] layout.
+pub trait MetadataField: Sized {
+    /// Returns the raw bits of the value.
+    fn into_bits(self) -> u64;
+
+    /// Creates the value from raw bits, `None` if they are invalid.
+    fn from_bits(bits: u64) -> Option<Self>;
+}
+
+macro_rules! impl_metadata_field {
+    ($($ty:ty),*) => {
+        $(
+            impl MetadataField for $ty {
+                fn into_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64) -> Option<Self> {
+                    <$ty>::try_from(bits).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_metadata_field!(u8, u16, u32, u64);
+
+impl MetadataField for bool {
+    fn into_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn from_bits(bits: u64) -> Option<Self> {
+        match bits {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// Error encoding or decoding message metadata.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum MetadataError {
+    /// The value of the field does not fit its width.
+    Overflow(&'static str),
+    /// The bits of the field are no valid value.
+    Invalid(&'static str),
+    /// Bits outside of the layout are set.
+    UnusedBits,
+    /// The kernel refused the metadata.
+    Kernel(PxError_t),
+}
+
+impl From<PxError_t> for MetadataError {
+    fn from(error: PxError_t) -> Self {
+        MetadataError::Kernel(error)
+    }
+}
+
+impl<T, A> Message<T, A> {
+    /// Encodes and sets the metadata of the message.
+    pub fn set_metadata<M: Metadata>(&mut self, metadata: &M) -> Result<(), MetadataError> {
+        let bits = metadata.encode()?;
+        PxResult::from(PxMsgSetMetadata(self.handle, PxMsgMetadata_t(bits)))?;
+        Ok(())
+    }
+
+    /// Returns the decoded metadata of the message.
+    pub fn metadata<M: Metadata>(&self) -> Result<M, MetadataError> {
+        M::decode(PxMsgGetMetadata(self.handle).0)
+    }
+}
+
+/// Packs `value` into `width` bits at `offset` and advances the offset.
+#[doc(hidden)]
+pub fn pack(
+    bits: &mut u64,
+    offset: &mut u32,
+    width: u32,
+    value: u64,
+    field: &'static str,
+) -> Result<(), MetadataError> {
+    if value & !mask(width) != 0 {
+        return Err(MetadataError::Overflow(field));
+    }
+    *bits |= value.checked_shl(*offset).unwrap_or(0);
+    *offset += width;
+    Ok(())
+}
+
+/// Unpacks `width` bits at `offset` and advances the offset.
+#[doc(hidden)]
+pub fn unpack(bits: u64, offset: &mut u32, width: u32) -> u64 {
+    let value = bits.checked_shr(*offset).unwrap_or(0) & mask(width);
+    *offset += width;
+    value
+}
+
+/// Checks that no bits above the first `width` ones are set.
+#[doc(hidden)]
+pub fn check_unused(bits: u64, width: u32) -> Result<(), MetadataError> {
+    if bits & !mask(width) != 0 {
+        return Err(MetadataError::UnusedBits);
+    }
+    Ok(())
+}
+
+/// Places fields of the given widths one after another from the least significant bit on.
+#[doc(hidden)]
+pub const fn layout<const N: usize>(
+    names: [&'static str; N],
+    widths: [u32; N],
+) -> [FieldLayout; N] {
+    let mut fields = [FieldLayout {
+        name: "",
+        offset: 0,
+        width: 0,
+    }; N];
+    let mut offset = 0;
+    let mut index = 0;
+    while index < N {
+        fields[index] = FieldLayout {
+            name: names[index],
+            offset,
+            width: widths[index],
+        };
+        offset += widths[index];
+        index += 1;
+    }
+    fields
+}
+
+const fn mask(width: u32) -> u64 {
+    if width >= u64::BITS {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Defines a struct implementing [Metadata] from fields with bit widths.
+///
+/// Every field needs a `#[bits(n)]` attribute before its other attributes.
+/// The widths must add up to at most 64 bits. See the [module documentation](crate::msg::metadata).
+#[macro_export]
+macro_rules! message_metadata {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                #[bits($bits:literal)]
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            ::core::marker::Copy,
+            ::core::clone::Clone,
+            ::core::fmt::Debug,
+            ::core::cmp::PartialEq,
+            ::core::cmp::Eq,
+        )]
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        const _: () = ::core::assert!(
+            0 $(+ $bits)* <= 64,
+            ::core::concat!("metadata layout of `", ::core::stringify!($name), "` exceeds 64 bits")
+        );
+
+        impl $crate::msg::metadata::Metadata for $name {
+            const BITS: u32 = 0 $(+ $bits)*;
+
+            const FIELDS: &'static [$crate::msg::metadata::FieldLayout] =
+                &$crate::msg::metadata::layout([$(::core::stringify!($field)),*], [$($bits),*]);
+
+            fn encode(&self) -> ::core::result::Result<u64, $crate::msg::metadata::MetadataError> {
+                let mut bits = 0;
+                let mut offset = 0;
+                $(
+                    $crate::msg::metadata::pack(
+                        &mut bits,
+                        &mut offset,
+                        $bits,
+                        $crate::msg::metadata::MetadataField::into_bits(self.$field),
+                        ::core::stringify!($field),
+                    )?;
+                )*
+                let _ = offset;
+                ::core::result::Result::Ok(bits)
+            }
+
+            fn decode(
+                bits: u64,
+            ) -> ::core::result::Result<Self, $crate::msg::metadata::MetadataError> {
+                $crate::msg::metadata::check_unused(bits, Self::BITS)?;
+                let mut offset = 0;
+                $(
+                    let $field = <$ty as $crate::msg::metadata::MetadataField>::from_bits(
+                        $crate::msg::metadata::unpack(bits, &mut offset, $bits),
+                    )
+                    .ok_or($crate::msg::metadata::MetadataError::Invalid(::core::stringify!($field)))?;
+                )*
+                let _ = offset;
+                ::core::result::Result::Ok(Self { $($field),* })
+            }
+        }
+
+        impl $crate::__private::defmt::Format for $name {
+            fn format(&self, fmt: $crate::__private::defmt::Formatter) {
+                $crate::__private::format_open(fmt, ::core::stringify!($name));
+                $(
+                    $crate::__private::format_field(fmt, ::core::stringify!($field), &self.$field);
+                )*
+                $crate::__private::format_close(fmt);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Aliases shadowing the prelude must not break the generated code.
+    #[allow(dead_code)]
+    type Result<T> = core::result::Result<T, ()>;
+
+    crate::message_metadata! {
+        struct Header {
+            #[bits(8)]
+            kind: u8,
+            #[bits(1)]
+            urgent: bool,
+            #[bits(20)]
+            seq: u32,
+        }
+    }
+
+    #[test]
+    fn pack_and_unpack_round_trip() {
+        let mut bits = 0;
+        let mut offset = 0;
+        pack(&mut bits, &mut offset, 4, 0xA, "a").unwrap();
+        pack(&mut bits, &mut offset, 60, 0x0123_4567_89AB_CDEF, "b").unwrap();
+        assert_eq!(offset, 64);
+        assert_eq!(bits, 0x1234_5678_9ABC_DEFA);
+
+        let mut offset = 0;
+        assert_eq!(unpack(bits, &mut offset, 4), 0xA);
+        assert_eq!(unpack(bits, &mut offset, 60), 0x0123_4567_89AB_CDEF);
+        assert_eq!(offset, 64);
+    }
+
+    #[test]
+    fn pack_rejects_values_wider_than_the_field() {
+        let mut bits = 0;
+        let mut offset = 0;
+        assert_eq!(
+            pack(&mut bits, &mut offset, 3, 8, "kind"),
+            Err(MetadataError::Overflow("kind"))
+        );
+        assert_eq!((bits, offset), (0, 0));
+        pack(&mut bits, &mut offset, 64, u64::MAX, "all").unwrap();
+        assert_eq!(bits, u64::MAX);
+    }
+
+    #[test]
+    fn check_unused_rejects_bits_outside_the_layout() {
+        assert_eq!(check_unused(0xFF, 8), Ok(()));
+        assert_eq!(check_unused(0x100, 8), Err(MetadataError::UnusedBits));
+        assert_eq!(check_unused(u64::MAX, 64), Ok(()));
+    }
+
+    #[test]
+    fn generated_layout_round_trips() {
+        let header = Header {
+            kind: 0x12,
+            urgent: true,
+            seq: 0xF_FFFF,
+        };
+        assert_eq!(Header::BITS, 29);
+        let bits = header.encode().unwrap();
+        assert_eq!(bits, 0x12 | 1 << 8 | 0xF_FFFF << 9);
+        assert_eq!(Header::decode(bits), Ok(header));
+    }
+
+    #[test]
+    fn generated_layout_lists_fields_in_bit_order() {
+        let fields: Vec<_> = Header::FIELDS
+            .iter()
+            .map(|field| (field.name, field.offset, field.width))
+            .collect();
+        assert_eq!(fields, [("kind", 0, 8), ("urgent", 8, 1), ("seq", 9, 20)]);
+        assert_eq!(Header::FIELDS[2].mask(), 0xF_FFFF);
+    }
+
+    #[test]
+    fn generated_layout_rejects_invalid_values() {
+        let header = Header {
+            kind: 0,
+            urgent: false,
+            seq: 1 << 20,
+        };
+        assert_eq!(header.encode(), Err(MetadataError::Overflow("seq")));
+        assert_eq!(Header::decode(1 << 29), Err(MetadataError::UnusedBits));
+    }
+}
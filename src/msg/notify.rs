@@ -0,0 +1,115 @@
+//! Mailbox handlers turning arriving messages into task events.
+//!
+//! The kernel blocks a task on one mailbox at a time. To wait on several
+//! mailboxes, a handler installed with [PxMbxInstallHnd] runs in the context
+//! of every sender and signals an event to the waiting task, which then
+//! checks its mailboxes with [PxMsgReceive_NoWait]. The handler returns the
+//! message, so it is queued in the mailbox as usual.
+//!
+//! A mailbox has a single handler, so only one waiter can be notified per
+//! mailbox at a time.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use super::Mailbox;
+use crate::bindings::*;
+use crate::events::Events;
+use crate::PxResult;
+
+/// Handler called with the message and its argument for every message sent to a mailbox.
+///
+/// Handlers run in handler context, so they may only use the `_Hnd` variants
+/// of the kernel API. The returned message is queued in the mailbox.
+pub(crate) type MailboxHandler = unsafe extern "C" fn(PxMsg_t, PxArg_t) -> PxMsg_t;
+
+impl Mailbox {
+    /// Installs `handler` to be called with `arg` for every message sent to the mailbox.
+    ///
+    /// Replaces a previously installed handler.
+    ///
+    /// # Safety
+    /// `handler` must be safe to call with `arg` until it is removed with
+    /// [`Mailbox::remove_handler`].
+    pub(crate) unsafe fn install_handler(
+        &self,
+        handler: MailboxHandler,
+        arg: PxArg_t,
+    ) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS, the caller keeps `arg` valid.
+        unsafe { PxMbxInstallHnd(*self, Some(handler), PxMbxReq_t::PXMbxReqMsg, arg) }.into()
+    }
+
+    /// Removes the installed handler.
+    pub(crate) fn remove_handler(&self) {
+        // # Safety
+        // No handler is installed, the argument is not used.
+        let _ = unsafe { PxMbxInstallHnd(*self, None, PxMbxReq_t::PXMbxReqMsg, PxArg_t(0)) };
+    }
+}
+
+/// Task and events signalled by [Notify::handler].
+pub(crate) struct Notify {
+    task: PxTask_t,
+    events: Events,
+}
+
+impl Notify {
+    /// Notifies the calling task with `events`.
+    pub(crate) fn current(events: Events) -> Self {
+        Self {
+            task: PxGetId(),
+            events,
+        }
+    }
+
+    /// Installs the notification on all `mailboxes` until the guard is dropped.
+    ///
+    /// The notification must stay in place while the guard exists, which the
+    /// borrow ensures.
+    pub(crate) fn install<'a>(&'a self, mailboxes: &'a [Mailbox]) -> PxResult<NotifyGuard<'a>> {
+        let mut guard = NotifyGuard {
+            mailboxes,
+            installed: 0,
+            _notify: self,
+        };
+        for mailbox in mailboxes {
+            // # Safety
+            // The argument points to `self`, which outlives the guard removing the handler.
+            unsafe { mailbox.install_handler(Self::handler, PxArg_t(self as *const Self as _)) }?;
+            guard.installed += 1;
+        }
+        Ok(guard)
+    }
+
+    /// Signals the events of the [Notify] `arg` points to.
+    ///
+    /// # Safety
+    /// `arg` must point to a valid [Notify].
+    unsafe extern "C" fn handler(message: PxMsg_t, arg: PxArg_t) -> PxMsg_t {
+        // # Safety
+        // The caller guarantees that `arg` points to a valid `Notify`.
+        let notify = unsafe { &*(arg.0 as usize as *const Notify) };
+        // # Safety
+        // Parameters are copied and checked by PXROS, the handler runs in handler context.
+        let _ = unsafe { PxTaskSignalEvents_Hnd(notify.task, notify.events) };
+        message
+    }
+}
+
+/// Removes the handlers installed by [`Notify::install`] on drop.
+pub(crate) struct NotifyGuard<'a> {
+    mailboxes: &'a [Mailbox],
+    installed: usize,
+    _notify: &'a Notify,
+}
+
+impl Drop for NotifyGuard<'_> {
+    fn drop(&mut self) {
+        for mailbox in &self.mailboxes[..self.installed] {
+            mailbox.remove_handler();
+        }
+    }
+}
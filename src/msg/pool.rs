@@ -0,0 +1,201 @@
+//! Fixed-size message pools built on release mailboxes.
+//!
+//! Every message of a pool has the pool mailbox installed as its release
+//! mailbox via [PxMsgInstallRelmbx]. Releasing such a message, e.g. by
+//! dropping the [Message] on the receiving side, parks it in the pool again
+//! instead of returning its memory to the memory class. Taken messages are
+//! [PoolMessage]s, which keep the pool mailbox installed.
+//!
+//! The kernel does not expose the fill level of a mailbox, and counting the
+//! parked messages would take them from the pool while other tasks try to
+//! take one, so the pool only reports its [capacity](MessagePool::capacity).
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use super::{Mailbox, Message, Metadata, MetadataError, PendingRelease, Plain};
+use crate::bindings::*;
+use crate::PxResult;
+
+/// Pool of `N` pre-allocated messages carrying a `T`.
+///
+/// The pool is never released, as its messages may outlive the handle.
+pub struct MessagePool<T, const N: usize> {
+    mailbox: Mailbox,
+    marker: PhantomData<T>,
+}
+
+impl<T: Plain, const N: usize> MessagePool<T, N> {
+    /// Creates the pool mailbox and fills it with `N` messages initialized with `init`.
+    ///
+    /// Messages are requested from `mc`, the mailbox and message objects from `opool`.
+    pub fn new(init: T, mc: PxMc_t, opool: impl Into<PxOpool_t>) -> PxResult<Self> {
+        let opool = opool.into();
+        let mailbox = Mailbox::request(opool)?;
+
+        // Request all messages before installing the release mailbox, so a
+        // failing request releases the others back to the memory class.
+        let messages: [PxResult<Message<T>>; N] =
+            core::array::from_fn(|_| Message::new(init, mc, opool));
+        if let Some(Err(error)) = messages.iter().find(|message| message.is_err()) {
+            let error = *error;
+            drop(messages);
+            let _ = mailbox.release();
+            return Err(error);
+        }
+
+        // Dropping a message on failure releases it to the memory class, or to
+        // the pool mailbox once its release mailbox is installed.
+        let mut result = Ok(());
+        for message in messages.into_iter().flatten() {
+            if result.is_ok() {
+                result = message
+                    .install_release_mailbox(mailbox)
+                    .and_then(|_| message.send(mailbox).map_err(|(error, _)| error));
+            }
+        }
+        if let Err(error) = result {
+            Self::discard(mailbox);
+            return Err(error);
+        }
+
+        Ok(Self {
+            mailbox,
+            marker: PhantomData,
+        })
+    }
+
+    /// Takes a message from the pool and writes `value` into it, blocking until one is available.
+    pub fn take(&self, value: T) -> PxResult<PoolMessage<T>> {
+        self.reclaim(PxMsgReceive(self.mailbox), value)
+    }
+
+    /// Takes a message from the pool and writes `value` into it if one is available.
+    pub fn try_take(&self, value: T) -> PxResult<Option<PoolMessage<T>>> {
+        let handle = PxMsgReceive_NoWait(self.mailbox).checked()?;
+        if handle.is_valid() {
+            self.reclaim(handle, value).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the total number of messages in the pool.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the pool mailbox.
+    pub const fn mailbox(&self) -> Mailbox {
+        self.mailbox
+    }
+
+    /// Frees the messages parked in a pool mailbox that failed to fill and releases it.
+    fn discard(mailbox: Mailbox) {
+        while let Ok(handle) = PxMsgReceive_NoWait(mailbox).checked() {
+            if !handle.is_valid() {
+                break;
+            }
+            // An invalid mailbox removes the release mailbox, so releasing the
+            // message returns it to its memory class.
+            let _ = PxMsgInstallRelmbx(handle, Mailbox::invalid());
+            // # Safety
+            // The message was received from the pool mailbox and is owned by us.
+            let _ = unsafe { PxMsgRelease(handle) };
+        }
+        let _ = mailbox.release();
+    }
+
+    /// Makes a message that came back to the pool writable again and writes `value` into it.
+    ///
+    /// The message may have been handed out read-only before it was released.
+    fn reclaim(&self, handle: PxMsg_t, value: T) -> PxResult<PoolMessage<T>> {
+        let handle = handle.checked()?;
+        if let Err(error) =
+            PxResult::from(PxMsgSetProtection(handle, PxProtectType_t::WRProtection))
+        {
+            let _ = PxMsgSend(handle, self.mailbox);
+            return Err(error);
+        }
+        // # Safety
+        // Messages taken from the pool mailbox are owned by the taker only.
+        let mut message = unsafe { Message::from_handle(handle) }?;
+        *message.data_mut() = value;
+        Ok(PoolMessage { message })
+    }
+}
+
+/// Message taken from a [MessagePool], parked in the pool again once released.
+///
+/// The release mailbox of the message belongs to the pool, so it offers no
+/// [`Message::send_released`], which would replace it. It only dereferences
+/// to the [Message] immutably, so the message cannot be swapped out.
+#[must_use = "Dropping a message returns it to the pool"]
+pub struct PoolMessage<T> {
+    message: Message<T>,
+}
+
+impl<T: Plain> PoolMessage<T> {
+    /// Returns the message data mutably.
+    pub fn data_mut(&mut self) -> &mut T {
+        self.message.data_mut()
+    }
+
+    /// Encodes and sets the metadata of the message.
+    pub fn set_metadata<M: Metadata>(&mut self, metadata: &M) -> Result<(), MetadataError> {
+        self.message.set_metadata(metadata)
+    }
+
+    /// Sends the message to a mailbox.
+    ///
+    /// Returns the error and the message if the kernel refused it.
+    pub fn send(self, mailbox: Mailbox) -> Result<(), (PxError_t, Self)> {
+        self.message
+            .send(mailbox)
+            .map_err(|(error, message)| (error, Self { message }))
+    }
+
+    /// Sends the message to a mailbox, ahead of normal priority messages.
+    pub fn send_prio(self, mailbox: Mailbox) -> Result<(), (PxError_t, Self)> {
+        self.message
+            .send_prio(mailbox)
+            .map_err(|(error, message)| (error, Self { message }))
+    }
+
+    /// Sends the message to a mailbox and marks it to be handed back once released.
+    ///
+    /// The message keeps the pool as release mailbox, so it returns to the
+    /// pool once the [PendingRelease] releases it for good.
+    pub fn send_await_release(
+        self,
+        mailbox: Mailbox,
+    ) -> Result<PendingRelease<T>, (PxError_t, Self)> {
+        self.message
+            .send_await_release(mailbox)
+            .map_err(|(error, message)| (error, Self { message }))
+    }
+}
+
+impl<T> Deref for PoolMessage<T> {
+    type Target = Message<T>;
+
+    fn deref(&self) -> &Message<T> {
+        &self.message
+    }
+}
+
+impl<T> defmt::Format for PoolMessage<T> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "PoolMessage({})", self.message.handle())
+    }
+}
+
+impl<T, const N: usize> defmt::Format for MessagePool<T, N> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "MessagePool({}, capacity: {})", self.mailbox, N)
+    }
+}
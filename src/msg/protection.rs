@@ -0,0 +1,84 @@
+//! Access typestate of messages over [PxMsgSetProtection] and [PxMsgGetProtection].
+//!
+//! A [`Message<T, ReadWrite>`](Message) exposes `&mut T`, a
+//! [`Message<T, ReadOnly>`](Message) only `&T`. The owner hands out read-only
+//! messages with [`Message::into_read_only`], which sets the kernel protection
+//! before the type changes. Received messages are read-only until
+//! [`Message::writable`] confirmed that the sender left them writable.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use super::{Message, Plain};
+use crate::bindings::*;
+use crate::PxResult;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Access a message handle grants to its buffer.
+pub trait Access: sealed::Sealed {
+    /// Checks if the kernel protection of a message allows this access.
+    fn permits(protection: PxProtectType_t) -> bool;
+}
+
+/// Access marker for messages whose buffer may be written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadWrite {}
+
+/// Access marker for messages whose buffer may only be read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadOnly {}
+
+impl sealed::Sealed for ReadWrite {}
+impl sealed::Sealed for ReadOnly {}
+
+impl Access for ReadWrite {
+    fn permits(protection: PxProtectType_t) -> bool {
+        protection == PxProtectType_t::WRProtection
+    }
+}
+
+impl Access for ReadOnly {
+    fn permits(protection: PxProtectType_t) -> bool {
+        matches!(
+            protection,
+            PxProtectType_t::ReadProtection | PxProtectType_t::WRProtection
+        )
+    }
+}
+
+impl<T: Plain, A: Access> Message<T, A> {
+    /// Returns the kernel protection of the message buffer.
+    pub fn protection(&self) -> PxProtectType_t {
+        PxMsgGetProtection(self.handle)
+    }
+}
+
+impl<T: Plain> Message<T, ReadWrite> {
+    /// Protects the buffer against writes, so holders of the message can only read it.
+    ///
+    /// Returns the error and the message if the kernel refused the protection.
+    pub fn into_read_only(self) -> Result<Message<T, ReadOnly>, (PxError_t, Self)> {
+        match PxResult::from(PxMsgSetProtection(
+            self.handle,
+            PxProtectType_t::ReadProtection,
+        )) {
+            Ok(()) => Ok(self.cast()),
+            Err(error) => Err((error, self)),
+        }
+    }
+}
+
+impl<T: Plain> Message<T, ReadOnly> {
+    /// Returns the message with write access if its buffer is not write protected.
+    pub fn writable(self) -> Result<Message<T, ReadWrite>, Self> {
+        if ReadWrite::permits(self.protection()) {
+            Ok(self.cast())
+        } else {
+            Err(self)
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Asynchronous message receive.
+//!
+//! [`Mailbox::recv`] returns a [Recv] future. Polled by an
+//! [Executor](crate::executor::Executor), it installs a mailbox handler with
+//! [PxMbxInstallHnd] that wakes its executor slot whenever a message is sent
+//! to the mailbox, and receives with [PxMsgReceive_NoWait] when polled. The
+//! handler is removed once the future completes or is dropped, so a mailbox is
+//! awaited by one future at a time.
+//!
+//! Messages are only taken from the mailbox when the future completes, so
+//! cancelling a receive never loses a message.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use super::{Mailbox, Message, Plain, ReadOnly};
+use crate::bindings::*;
+use crate::executor::{self, Slot};
+use crate::PxResult;
+
+impl Mailbox {
+    /// Receives the next message asynchronously.
+    ///
+    /// Outside of an [Executor](crate::executor::Executor) the future cannot
+    /// be woken by the mailbox and wakes itself to be polled again.
+    pub fn recv<T: Plain>(&self) -> Recv<T> {
+        Recv {
+            wait: MailboxWait::new(*self),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Future returned by [`Mailbox::recv`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<T: Plain> {
+    wait: MailboxWait,
+    marker: PhantomData<T>,
+}
+
+// The message is only referenced by its handle.
+impl<T: Plain> Unpin for Recv<T> {}
+
+impl<T: Plain> Future for Recv<T> {
+    type Output = PxResult<Message<T, ReadOnly>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut()
+            .wait
+            .poll_receive(cx)
+            // # Safety
+            // A received message is owned by the receiver only.
+            .map(|handle| handle.and_then(|handle| unsafe { Message::from_handle(handle) }))
+    }
+}
+
+impl<T: Plain> defmt::Format for Recv<T> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Recv({})", self.wait.mailbox)
+    }
+}
+
+/// Receive of a raw message woken by a mailbox handler.
+pub(crate) struct MailboxWait {
+    mailbox: Mailbox,
+    slot: Option<&'static Slot>,
+}
+
+impl MailboxWait {
+    pub(crate) const fn new(mailbox: Mailbox) -> Self {
+        Self {
+            mailbox,
+            slot: None,
+        }
+    }
+
+    pub(crate) const fn mailbox(&self) -> Mailbox {
+        self.mailbox
+    }
+
+    /// Takes the next message of the mailbox or arranges to be woken once one arrives.
+    pub(crate) fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<PxResult<PxMsg_t>> {
+        if let Some(result) = self.try_receive() {
+            return Poll::Ready(result);
+        }
+
+        match executor::slot_of(cx.waker()) {
+            Some(slot) if self.slot.is_none() => {
+                if let Err(error) = slot.watch(self.mailbox) {
+                    return Poll::Ready(Err(error));
+                }
+                self.slot = Some(slot);
+                // A message sent before the handler was installed did not wake the slot.
+                if let Some(result) = self.try_receive() {
+                    return Poll::Ready(result);
+                }
+            }
+            Some(_) => {}
+            None => cx.waker().wake_by_ref(),
+        }
+        Poll::Pending
+    }
+
+    fn try_receive(&mut self) -> Option<PxResult<PxMsg_t>> {
+        let result = match PxMsgReceive_NoWait(self.mailbox).checked() {
+            Ok(handle) if !handle.is_valid() => return None,
+            result => result,
+        };
+        self.finish();
+        Some(result)
+    }
+
+    /// Removes the mailbox handler once the wait is over.
+    pub(crate) fn finish(&mut self) {
+        if self.slot.take().is_some() {
+            self.mailbox.remove_handler();
+        }
+    }
+}
+
+impl Drop for MailboxWait {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
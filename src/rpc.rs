@@ -0,0 +1,367 @@
+//! Request/response calls over mailboxes.
+//!
+//! A [Client] sends a typed request to the mailbox of a [Server] and waits
+//! for the typed reply. Requests carry an [RpcHeader] in their metadata with
+//! the request kind, a sequence number to correlate the reply and the mailbox
+//! the reply is sent to. Requests without a reply mailbox, as C tasks may
+//! send them, are answered to the mailbox of the sending task.
+//!
+//! Requests and replies have one type each. A server handling several request
+//! kinds uses unions whose variant is selected by the kind:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Copy, Clone)]
+//! union Request {
+//!     read: Read,
+//!     write: Write,
+//! }
+//! // # Safety
+//! // All variants are plain data.
+//! unsafe impl Plain for Request {}
+//!
+//! // Server task
+//! fn read(request: &Request) -> Value { let read = unsafe { request.read }; ... }
+//! fn write(request: &Request) -> Value { let write = unsafe { request.write }; ... }
+//! let handlers = [(READ, read as Handler<_, _>), (WRITE, write)];
+//! let mut server = Server::new(mailbox, handlers, mc, opool);
+//! server.run();
+//!
+//! // Client task
+//! let mut client = Client::<Request, Value>::new(server_mailbox, mc, opool)?;
+//! let value = client.call_timeout(READ, Request { read: Read { address } }, &timeout)?;
+//! ```
+//!
+//! Servers keeping state between requests answer them with their own [Dispatch]
+//! instead of an array of handlers.
+//!
+//! [service!](crate::service) generates these unions with typed clients and
+//! servers of a service together with a C header, see [service].
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::marker::PhantomData;
+
+use crate::bindings::*;
+use crate::delay::Timeout;
+use crate::msg::{Mailbox, Message, MetadataError, Plain, ReadOnly};
+use crate::PxResult;
+
+pub mod service;
+
+crate::message_metadata! {
+    /// Metadata of RPC requests and replies.
+    pub struct RpcHeader {
+        #[bits(16)]
+        /// Request kind selecting the server handler.
+        pub kind: u16,
+        #[bits(16)]
+        /// Sequence number, echoed in the reply.
+        pub seq: u16,
+        #[bits(1)]
+        /// Set in replies if the server has no handler for the kind or could
+        /// not read the request.
+        pub failed: bool,
+        #[bits(1)]
+        /// Whether `reply_mailbox` is valid.
+        pub has_reply_mailbox: bool,
+        #[bits(16)]
+        /// Raw handle of the mailbox the reply is sent to.
+        pub reply_mailbox: u16,
+    }
+}
+
+/// Error of an RPC call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum RpcError {
+    /// A kernel call failed.
+    Kernel(PxError_t),
+    /// The header could not be encoded or decoded.
+    Metadata(MetadataError),
+    /// No reply arrived in time.
+    Timeout,
+    /// The server has no handler for the request kind.
+    UnknownKind(u16),
+    /// The server answered the request of this kind with a failed reply.
+    Failed(u16),
+}
+
+impl From<PxError_t> for RpcError {
+    fn from(error: PxError_t) -> Self {
+        RpcError::Kernel(error)
+    }
+}
+
+impl From<MetadataError> for RpcError {
+    fn from(error: MetadataError) -> Self {
+        RpcError::Metadata(error)
+    }
+}
+
+/// Client calling a [Server] with requests `Req` answered by `Resp`.
+///
+/// Replies are received in a mailbox only used by the client, messages
+/// arriving there that do not answer the current call are released.
+pub struct Client<Req, Resp> {
+    server: Mailbox,
+    reply: Mailbox,
+    owns_reply: bool,
+    seq: u16,
+    mc: PxMc_t,
+    opool: PxOpool_t,
+    marker: PhantomData<(Req, Resp)>,
+}
+
+impl<Req: Plain, Resp: Plain> Client<Req, Resp> {
+    /// Creates a client of the server mailbox with its own reply mailbox.
+    ///
+    /// The reply mailbox and the request messages are requested from `mc`
+    /// and `opool`, the mailbox is released when the client is dropped.
+    pub fn new(server: Mailbox, mc: PxMc_t, opool: impl Into<PxOpool_t>) -> PxResult<Self> {
+        let opool = opool.into();
+        let reply = Mailbox::request(opool)?;
+        let mut client = Self::with_reply_mailbox(server, reply, mc, opool);
+        client.owns_reply = true;
+        Ok(client)
+    }
+
+    /// Creates a client of the server mailbox receiving replies in `reply`.
+    ///
+    /// The mailbox must only receive replies for this client, other messages
+    /// arriving there are released.
+    pub fn with_reply_mailbox(
+        server: Mailbox,
+        reply: Mailbox,
+        mc: PxMc_t,
+        opool: impl Into<PxOpool_t>,
+    ) -> Self {
+        Self {
+            server,
+            reply,
+            owns_reply: false,
+            seq: 0,
+            mc,
+            opool: opool.into(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the mailbox replies are received in.
+    pub const fn reply_mailbox(&self) -> Mailbox {
+        self.reply
+    }
+
+    /// Sends a request of `kind` and blocks until the reply arrives.
+    pub fn call(&mut self, kind: u16, request: Req) -> Result<Message<Resp, ReadOnly>, RpcError> {
+        self.call_inner(kind, request, None)
+    }
+
+    /// Sends a request of `kind` and blocks until the reply arrives or `timeout` elapsed.
+    ///
+    /// The timeout is restarted for the call and stopped afterwards. A reply
+    /// arriving after the timeout is dropped by a later call.
+    pub fn call_timeout(
+        &mut self,
+        kind: u16,
+        request: Req,
+        timeout: &Timeout,
+    ) -> Result<Message<Resp, ReadOnly>, RpcError> {
+        timeout.events().reset();
+        timeout.start()?;
+        let reply = self.call_inner(kind, request, Some(timeout));
+        let _ = timeout.stop();
+        timeout.events().reset();
+        reply
+    }
+
+    fn call_inner(
+        &mut self,
+        kind: u16,
+        request: Req,
+        timeout: Option<&Timeout>,
+    ) -> Result<Message<Resp, ReadOnly>, RpcError> {
+        self.seq = self.seq.wrapping_add(1);
+        let reply_mailbox = u16::try_from(self.reply.as_raw())
+            .map_err(|_| MetadataError::Overflow("reply_mailbox"))?;
+        let header = RpcHeader {
+            kind,
+            seq: self.seq,
+            failed: false,
+            has_reply_mailbox: true,
+            reply_mailbox,
+        };
+
+        let mut message = Message::new(request, self.mc, self.opool)?;
+        message.set_metadata(&header)?;
+        message.send(self.server).map_err(|(error, _)| error)?;
+
+        let mailbox = self.reply;
+        loop {
+            let handle = match timeout {
+                Some(timeout) => {
+                    let result = PxMsgReceive_EvWait(mailbox, timeout.events());
+                    if !result.msg.is_valid() {
+                        if result.events.intersects(timeout.events()) {
+                            return Err(RpcError::Timeout);
+                        }
+                        // Without a timeout event the receive itself failed.
+                        let error = result.msg.checked().err();
+                        return Err(RpcError::Kernel(
+                            error.unwrap_or(PxError_t::PXERR_MSG_ILLMSG),
+                        ));
+                    }
+                    result.msg
+                }
+                None => PxMsgReceive(mailbox).checked()?,
+            };
+            // Replies to earlier, timed out calls and foreign messages are released.
+            // # Safety
+            // A received message is owned by the receiver only.
+            let Ok(reply) = (unsafe { Message::<Resp, ReadOnly>::from_handle(handle) }) else {
+                continue;
+            };
+            let Ok(reply_header) = reply.metadata::<RpcHeader>() else {
+                continue;
+            };
+            if reply_header.seq != self.seq || reply_header.kind != kind {
+                continue;
+            }
+            if reply_header.failed {
+                return Err(RpcError::Failed(kind));
+            }
+            return Ok(reply);
+        }
+    }
+}
+
+impl<Req, Resp> Drop for Client<Req, Resp> {
+    fn drop(&mut self) {
+        if !self.owns_reply {
+            return;
+        }
+        // Late replies to timed out calls would keep the mailbox from being released.
+        while let Ok(Some(reply)) = self.reply.try_receive::<()>() {
+            drop(reply);
+        }
+        let _ = self.reply.release();
+    }
+}
+
+impl<Req, Resp> defmt::Format for Client<Req, Resp> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Client({}, seq: {})", self.server, self.seq)
+    }
+}
+
+/// Handler answering a request.
+pub type Handler<Req, Resp> = fn(&Req) -> Resp;
+
+/// Answers the requests of a [Server] by request kind.
+pub trait Dispatch<Req, Resp> {
+    /// Answers a request of `kind`, `None` if the kind is unknown.
+    fn dispatch(&mut self, kind: u16, request: &Req) -> Option<Resp>;
+}
+
+impl<Req, Resp, const N: usize> Dispatch<Req, Resp> for [(u16, Handler<Req, Resp>); N] {
+    fn dispatch(&mut self, kind: u16, request: &Req) -> Option<Resp> {
+        self.iter()
+            .find(|(handler_kind, _)| *handler_kind == kind)
+            .map(|(_, handler)| handler(request))
+    }
+}
+
+/// Server answering requests with a [Dispatch], such as an array of [Handler]s by kind.
+pub struct Server<Req, Resp, D> {
+    mailbox: Mailbox,
+    dispatch: D,
+    mc: PxMc_t,
+    opool: PxOpool_t,
+    marker: PhantomData<(Req, Resp)>,
+}
+
+impl<Req: Plain, Resp: Plain, D: Dispatch<Req, Resp>> Server<Req, Resp, D> {
+    /// Creates a server receiving requests in `mailbox`.
+    ///
+    /// Reply messages are requested from `mc` and `opool`.
+    pub fn new(mailbox: Mailbox, dispatch: D, mc: PxMc_t, opool: impl Into<PxOpool_t>) -> Self {
+        Self {
+            mailbox,
+            dispatch,
+            mc,
+            opool: opool.into(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the dispatch, for example to access the state of a service.
+    pub fn dispatch_mut(&mut self) -> &mut D {
+        &mut self.dispatch
+    }
+
+    /// Serves requests forever, dropping requests that fail.
+    pub fn run(&mut self) -> ! {
+        loop {
+            let _ = self.serve_one();
+        }
+    }
+
+    /// Blocks until a request arrives, answers it and returns its kind.
+    ///
+    /// Requests of unknown kinds and requests that can't be read as `Req` are
+    /// answered with a failed reply. Requests whose header can't be decoded are
+    /// dropped, as the reply could not be correlated.
+    pub fn serve_one(&mut self) -> Result<u16, RpcError> {
+        let message = self.mailbox.receive::<()>()?;
+        let header = message.metadata::<RpcHeader>()?;
+        let reply_to = if header.has_reply_mailbox {
+            PxMbx_t::from_raw(header.reply_mailbox as u32)
+        } else {
+            Mailbox::of_task(PxMsgGetSender(message.handle()))?
+        };
+        // # Safety
+        // The handle is taken from the received message, which is owned by
+        // the receiver only.
+        let (response, error) =
+            match unsafe { Message::<Req, ReadOnly>::from_handle(message.into_handle()) } {
+                Ok(request) => match self.dispatch.dispatch(header.kind, request.data()) {
+                    Some(response) => (Some(response), None),
+                    None => (None, Some(RpcError::UnknownKind(header.kind))),
+                },
+                Err(error) => (None, Some(RpcError::Kernel(error))),
+            };
+
+        let reply_header = RpcHeader {
+            failed: response.is_none(),
+            has_reply_mailbox: false,
+            reply_mailbox: 0,
+            ..header
+        };
+        let mut reply = match response {
+            Some(response) => Message::new(response, self.mc, self.opool)?,
+            // Failed replies carry no data, the client does not read the buffer.
+            // # Safety
+            // The message was just requested and is owned by nobody else.
+            None => unsafe {
+                Message::<Resp>::from_handle(PxMsgRequest(
+                    core::mem::size_of::<Resp>() as PxSize_t,
+                    self.mc,
+                    self.opool,
+                ))
+            }?,
+        };
+        reply.set_metadata(&reply_header)?;
+        reply.send(reply_to).map_err(|(error, _)| error)?;
+        match error {
+            Some(error) => Err(error),
+            None => Ok(header.kind),
+        }
+    }
+}
+
+impl<Req, Resp, D> defmt::Format for Server<Req, Resp, D> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Server({})", self.mailbox)
+    }
+}
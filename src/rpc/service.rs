@@ -0,0 +1,619 @@
+//! Inter-task services defined once for Rust and C tasks.
+//!
+//! [service!](crate::service) defines a service module with the request and
+//! reply structs of every method, their request kinds, a blocking [Client]
+//! stub, a [Server] dispatching to an instance of a `Service` trait and the
+//! [ServiceDef] describing the protocol. [write_header] emits the C header from that
+//! definition, so C tasks speak the same protocol:
+//!
+//! ```ignore
+//! // src/storage.rs
+//! pxros::service! {
+//!     /// Key-value storage.
+//!     pub service storage {
+//!         /// Reads a value.
+//!         READ = 1 => fn read(Read { key: u32 }) -> ReadReply { value: u32, found: u8 };
+//!         /// Writes a value.
+//!         WRITE = 2 => fn write(Write { key: u32, value: u32 }) -> WriteReply { stored: u8 };
+//!     }
+//! }
+//!
+//! struct Flash { ... }
+//! impl storage::Service for Flash {
+//!     fn read(&mut self, request: &storage::Read) -> storage::ReadReply { ... }
+//!     fn write(&mut self, request: &storage::Write) -> storage::WriteReply { ... }
+//! }
+//! storage::server(mailbox, Flash::new(), mc, opool).run();
+//!
+//! let mut client = storage::Client::new(server_mailbox, mc, opool)?;
+//! let reply = client.read(storage::Read { key }, Some(&timeout))?;
+//!
+//! // tests/header.rs, run with `cargo test` for the host
+//! #[test]
+//! fn writes_storage_header() {
+//!     let mut header = String::new();
+//!     pxros::rpc::service::write_header(&mut header, &app::storage::DEFINITION).unwrap();
+//!     std::fs::write("include/storage.h", header).unwrap();
+//! }
+//! ```
+//!
+//! A build script can't write the header, as it runs before the crate
+//! defining the service is compiled. The header is generated by a host test
+//! or a host tool depending on that crate and checked in for the C tasks.
+//!
+//! Requests and replies of all methods share one union type each, whose
+//! variant is selected by the request kind in the [RpcHeader].
+//! Field types implement [CType], which maps them to their C equivalent. As
+//! message data they are [Plain], `bool` fields are not supported because C
+//! tasks may write other values than 0 and 1.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::fmt::{self, Display, Write};
+
+use super::RpcHeader;
+use crate::msg::{Metadata, Plain};
+
+#[cfg(doc)]
+use super::{Client, Server};
+
+/// Type with the layout of a C type.
+///
+/// # Safety
+///
+/// The type must have the size and alignment of `NAME`, or of an array of
+/// `LEN` elements of it. Like all message data, it is [Plain].
+pub unsafe trait CType: Plain {
+    /// Name of the C type.
+    const NAME: &'static str;
+    /// Number of array elements, 0 for scalars.
+    const LEN: usize = 0;
+}
+
+macro_rules! impl_c_type {
+    ($($ty:ty => $name:literal),*) => {
+        $(
+            // # Safety
+            // The fixed-width integer and IEEE 754 types match their Rust equivalent.
+            unsafe impl CType for $ty {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+impl_c_type!(
+    u8 => "uint8_t", u16 => "uint16_t", u32 => "uint32_t", u64 => "uint64_t",
+    i8 => "int8_t", i16 => "int16_t", i32 => "int32_t", i64 => "int64_t",
+    f32 => "float", f64 => "double"
+);
+
+// # Safety
+// Nested arrays have the layout of the flattened array.
+unsafe impl<T: CType, const N: usize> CType for [T; N] {
+    const NAME: &'static str = T::NAME;
+    const LEN: usize = N * if T::LEN == 0 { 1 } else { T::LEN };
+}
+
+/// Field of a request or reply struct.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FieldDef {
+    /// Field name.
+    pub name: &'static str,
+    /// Name of the C type.
+    pub c_type: &'static str,
+    /// Number of array elements, 0 for scalars.
+    pub len: usize,
+}
+
+/// Request or reply struct.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StructDef {
+    /// Struct name.
+    pub name: &'static str,
+    /// Fields in declaration order.
+    pub fields: &'static [FieldDef],
+}
+
+/// Method of a service.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MethodDef {
+    /// Method name.
+    pub name: &'static str,
+    /// Name of the request kind constant.
+    pub kind_name: &'static str,
+    /// Request kind.
+    pub kind: u16,
+    /// Request struct.
+    pub request: StructDef,
+    /// Reply struct.
+    pub reply: StructDef,
+}
+
+/// Protocol of a service defined with [service!](crate::service).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ServiceDef {
+    /// Service module name.
+    pub name: &'static str,
+    /// Methods in declaration order.
+    pub methods: &'static [MethodDef],
+}
+
+/// Writes the C header of the service.
+///
+/// The header defines the request kinds as `<SERVICE>_<KIND>`, the structs as
+/// `<service>_<Struct>`, the unions `<service>_Request` and `<service>_Reply`
+/// and the bit layout of the [RpcHeader] metadata. Request messages of any kind
+/// must be at least `sizeof(<service>_Request)` bytes, the server replies with
+/// `sizeof(<service>_Reply)` bytes, which the header states in a comment.
+pub fn write_header(out: &mut impl Write, service: &ServiceDef) -> fmt::Result {
+    let name = service.name;
+    let upper = Upper(name);
+    writeln!(
+        out,
+        "/* Generated from the pxros service `{name}`, do not edit. */"
+    )?;
+    writeln!(out, "#ifndef PXROS_SERVICE_{upper}_H")?;
+    writeln!(out, "#define PXROS_SERVICE_{upper}_H")?;
+    writeln!(out)?;
+    writeln!(out, "#include <stdint.h>")?;
+    writeln!(out)?;
+    write_rpc_header(out)?;
+    writeln!(out)?;
+    for method in service.methods {
+        writeln!(
+            out,
+            "#define {upper}_{} {}u",
+            Upper(method.kind_name),
+            method.kind
+        )?;
+    }
+    for method in service.methods {
+        writeln!(out)?;
+        write_struct(out, name, &method.request)?;
+        writeln!(out)?;
+        write_struct(out, name, &method.reply)?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/* Requests of every kind must be at least sizeof({name}_Request) bytes. */"
+    )?;
+    write_union(out, service, "Request", |method| &method.request)?;
+    writeln!(out)?;
+    writeln!(out, "/* Replies are sizeof({name}_Reply) bytes. */")?;
+    write_union(out, service, "Reply", |method| &method.reply)?;
+    writeln!(out)?;
+    writeln!(out, "#endif /* PXROS_SERVICE_{upper}_H */")
+}
+
+fn write_struct(out: &mut impl Write, service: &str, def: &StructDef) -> fmt::Result {
+    writeln!(out, "typedef struct {{")?;
+    for field in def.fields {
+        match field.len {
+            0 => writeln!(out, "    {} {};", field.c_type, field.name)?,
+            len => writeln!(out, "    {} {}[{len}];", field.c_type, field.name)?,
+        }
+    }
+    writeln!(out, "}} {service}_{};", def.name)
+}
+
+fn write_union(
+    out: &mut impl Write,
+    service: &ServiceDef,
+    union: &str,
+    variant: impl Fn(&MethodDef) -> &StructDef,
+) -> fmt::Result {
+    let name = service.name;
+    writeln!(out, "typedef union {{")?;
+    for method in service.methods {
+        writeln!(out, "    {name}_{} {};", variant(method).name, method.name)?;
+    }
+    writeln!(out, "}} {name}_{union};")
+}
+
+/// Writes the layout of [RpcHeader], shared by all service headers.
+fn write_rpc_header(out: &mut impl Write) -> fmt::Result {
+    writeln!(out, "#ifndef PXROS_RPC_HEADER")?;
+    writeln!(out, "#define PXROS_RPC_HEADER")?;
+    for field in RpcHeader::FIELDS {
+        let name = Upper(field.name);
+        writeln!(out, "#define PXROS_RPC_{name}_SHIFT {}u", field.offset)?;
+        writeln!(out, "#define PXROS_RPC_{name}_MASK 0x{:X}ull", field.mask())?;
+    }
+    writeln!(out, "#endif /* PXROS_RPC_HEADER */")
+}
+
+/// Displays a name in upper case.
+struct Upper<'a>(&'a str);
+
+impl Display for Upper<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .chars()
+            .try_for_each(|c| f.write_char(c.to_ascii_uppercase()))
+    }
+}
+
+/// Checks that the request kinds of a service are unique.
+#[doc(hidden)]
+pub const fn unique_kinds(kinds: &[u16]) -> bool {
+    let mut i = 0;
+    while i < kinds.len() {
+        let mut j = i + 1;
+        while j < kinds.len() {
+            if kinds[i] == kinds[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Defines a service module shared by Rust and C tasks.
+///
+/// Every method declares its request kind constant, its name and the fields
+/// of its request and reply structs. The generated code only refers to items
+/// of the enclosing module through the field types. See the
+/// [module documentation](crate::rpc::service).
+#[macro_export]
+macro_rules! service {
+    (
+        $(#[$meta:meta])*
+        $vis:vis service $service:ident {
+            $(
+                $(#[$method_meta:meta])*
+                $kind_name:ident = $kind:literal => fn $method:ident(
+                    $request:ident { $($request_field:ident : $request_ty:ty),+ $(,)? }
+                ) -> $reply:ident { $($reply_field:ident : $reply_ty:ty),+ $(,)? };
+            )+
+        }
+    ) => {
+        $(#[$meta])*
+        $vis mod $service {
+            // Field types may refer to items of the enclosing module.
+            #[allow(unused_imports)]
+            use super::*;
+
+            $(
+                #[doc = ::core::concat!(
+                    "Request kind of [Service::",
+                    ::core::stringify!($method),
+                    "].",
+                )]
+                pub const $kind_name: u16 = $kind;
+            )+
+
+            const _: () = ::core::assert!(
+                $crate::rpc::service::unique_kinds(&[$($kind),+]),
+                ::core::concat!(
+                    "request kinds of service `",
+                    ::core::stringify!($service),
+                    "` are not unique"
+                )
+            );
+
+            /// Number of methods.
+            pub const METHODS: usize = [$($kind),+].len();
+
+            $(
+                #[doc = ::core::concat!("Request of [Service::", ::core::stringify!($method), "].")]
+                #[repr(C)]
+                #[derive(
+                    ::core::marker::Copy,
+                    ::core::clone::Clone,
+                    ::core::fmt::Debug,
+                    ::core::cmp::PartialEq,
+                )]
+                pub struct $request {
+                    $(pub $request_field: $request_ty,)+
+                }
+
+                #[doc = ::core::concat!("Reply of [Service::", ::core::stringify!($method), "].")]
+                #[repr(C)]
+                #[derive(
+                    ::core::marker::Copy,
+                    ::core::clone::Clone,
+                    ::core::fmt::Debug,
+                    ::core::cmp::PartialEq,
+                )]
+                pub struct $reply {
+                    $(pub $reply_field: $reply_ty,)+
+                }
+
+                impl $crate::__private::defmt::Format for $request {
+                    fn format(&self, fmt: $crate::__private::defmt::Formatter) {
+                        $crate::__private::format_open(fmt, ::core::stringify!($request));
+                        $(
+                            $crate::__private::format_field(
+                                fmt,
+                                ::core::stringify!($request_field),
+                                &self.$request_field,
+                            );
+                        )+
+                        $crate::__private::format_close(fmt);
+                    }
+                }
+
+                impl $crate::__private::defmt::Format for $reply {
+                    fn format(&self, fmt: $crate::__private::defmt::Formatter) {
+                        $crate::__private::format_open(fmt, ::core::stringify!($reply));
+                        $(
+                            $crate::__private::format_field(
+                                fmt,
+                                ::core::stringify!($reply_field),
+                                &self.$reply_field,
+                            );
+                        )+
+                        $crate::__private::format_close(fmt);
+                    }
+                }
+            )+
+
+            // # Safety
+            // The fields are `CType`, which implies `Plain`, as checked by `DEFINITION`.
+            $(
+                unsafe impl $crate::msg::Plain for $request {}
+                unsafe impl $crate::msg::Plain for $reply {}
+            )+
+
+            /// Requests of all methods, selected by the request kind.
+            #[repr(C)]
+            #[derive(::core::marker::Copy, ::core::clone::Clone)]
+            pub union Request {
+                $(pub $method: $request,)+
+            }
+
+            // # Safety
+            // All variants are `Plain`.
+            unsafe impl $crate::msg::Plain for Request {}
+
+            /// Replies of all methods, selected by the request kind.
+            #[repr(C)]
+            #[derive(::core::marker::Copy, ::core::clone::Clone)]
+            pub union Reply {
+                $(pub $method: $reply,)+
+            }
+
+            // # Safety
+            // All variants are `Plain`.
+            unsafe impl $crate::msg::Plain for Reply {}
+
+            /// Implementation of the service, called by the [server].
+            pub trait Service {
+                $(
+                    $(#[$method_meta])*
+                    fn $method(&mut self, request: &$request) -> $reply;
+                )+
+            }
+
+            /// Blocking client of the service.
+            pub struct Client {
+                inner: $crate::rpc::Client<Request, Reply>,
+            }
+
+            impl Client {
+                /// Creates a client of the server mailbox with its own reply mailbox.
+                ///
+                /// See [Client::new]($crate::rpc::Client::new).
+                pub fn new(
+                    server: $crate::msg::Mailbox,
+                    mc: $crate::bindings::PxMc_t,
+                    opool: impl ::core::convert::Into<$crate::bindings::PxOpool_t>,
+                ) -> $crate::PxResult<Self> {
+                    ::core::result::Result::Ok(Self {
+                        inner: $crate::rpc::Client::new(server, mc, opool)?,
+                    })
+                }
+
+                /// Creates a client of the server mailbox receiving replies in `reply`.
+                ///
+                /// See [Client::with_reply_mailbox]($crate::rpc::Client::with_reply_mailbox).
+                pub fn with_reply_mailbox(
+                    server: $crate::msg::Mailbox,
+                    reply: $crate::msg::Mailbox,
+                    mc: $crate::bindings::PxMc_t,
+                    opool: impl ::core::convert::Into<$crate::bindings::PxOpool_t>,
+                ) -> Self {
+                    Self {
+                        inner: $crate::rpc::Client::with_reply_mailbox(server, reply, mc, opool),
+                    }
+                }
+
+                $(
+                    $(#[$method_meta])*
+                    ///
+                    /// Blocks until the reply arrives or the optional timeout elapsed.
+                    pub fn $method(
+                        &mut self,
+                        request: $request,
+                        timeout: ::core::option::Option<&$crate::delay::Timeout>,
+                    ) -> ::core::result::Result<$reply, $crate::rpc::RpcError> {
+                        let request = Request { $method: request };
+                        let reply = match timeout {
+                            ::core::option::Option::Some(timeout) => {
+                                self.inner.call_timeout($kind_name, request, timeout)?
+                            }
+                            ::core::option::Option::None => self.inner.call($kind_name, request)?,
+                        };
+                        // # Safety
+                        // All variants are `Plain`, and the client only returns replies
+                        // of the request kind, which the server builds from this variant.
+                        ::core::result::Result::Ok(unsafe { reply.data().$method })
+                    }
+                )+
+            }
+
+            /// Dispatch of requests to an instance of [Service].
+            pub struct Dispatcher<S>(pub S);
+
+            impl<S: Service> $crate::rpc::Dispatch<Request, Reply> for Dispatcher<S> {
+                fn dispatch(
+                    &mut self,
+                    kind: u16,
+                    request: &Request,
+                ) -> ::core::option::Option<Reply> {
+                    match kind {
+                        $(
+                            $kind_name => ::core::option::Option::Some(Reply {
+                                // # Safety
+                                // All variants are `Plain`, and requests of this kind are
+                                // built from this variant by the client.
+                                $method: self.0.$method(unsafe { &request.$method }),
+                            }),
+                        )+
+                        _ => ::core::option::Option::None,
+                    }
+                }
+            }
+
+            /// Creates the server dispatching requests received in `mailbox` to `service`.
+            ///
+            /// Reply messages are requested from `mc` and `opool`.
+            pub fn server<S: Service>(
+                mailbox: $crate::msg::Mailbox,
+                service: S,
+                mc: $crate::bindings::PxMc_t,
+                opool: impl ::core::convert::Into<$crate::bindings::PxOpool_t>,
+            ) -> $crate::rpc::Server<Request, Reply, Dispatcher<S>> {
+                $crate::rpc::Server::new(mailbox, Dispatcher(service), mc, opool)
+            }
+
+            /// Protocol of the service, see
+            /// [write_header]($crate::rpc::service::write_header).
+            pub const DEFINITION: $crate::rpc::service::ServiceDef = $crate::rpc::service::ServiceDef {
+                name: ::core::stringify!($service),
+                methods: &[$(
+                    $crate::rpc::service::MethodDef {
+                        name: ::core::stringify!($method),
+                        kind_name: ::core::stringify!($kind_name),
+                        kind: $kind,
+                        request: $crate::rpc::service::StructDef {
+                            name: ::core::stringify!($request),
+                            fields: &[$(
+                                $crate::rpc::service::FieldDef {
+                                    name: ::core::stringify!($request_field),
+                                    c_type: <$request_ty as $crate::rpc::service::CType>::NAME,
+                                    len: <$request_ty as $crate::rpc::service::CType>::LEN,
+                                },
+                            )+],
+                        },
+                        reply: $crate::rpc::service::StructDef {
+                            name: ::core::stringify!($reply),
+                            fields: &[$(
+                                $crate::rpc::service::FieldDef {
+                                    name: ::core::stringify!($reply_field),
+                                    c_type: <$reply_ty as $crate::rpc::service::CType>::NAME,
+                                    len: <$reply_ty as $crate::rpc::service::CType>::LEN,
+                                },
+                            )+],
+                        },
+                    },
+                )+],
+            };
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::Dispatch;
+
+    crate::service! {
+        // Only the definition and the dispatch are used on the host.
+        #[allow(dead_code)]
+        service counter {
+            ADD = 3 => fn add(Add { amount: u32, tags: [u8; 2] }) -> AddReply { total: u64 };
+        }
+    }
+
+    struct Total(u64);
+
+    impl counter::Service for Total {
+        fn add(&mut self, request: &counter::Add) -> counter::AddReply {
+            self.0 += u64::from(request.amount);
+            counter::AddReply { total: self.0 }
+        }
+    }
+
+    #[test]
+    fn writes_header_of_service() {
+        let mut header = String::new();
+        write_header(&mut header, &counter::DEFINITION).unwrap();
+        assert_eq!(
+            header,
+            "/* Generated from the pxros service `counter`, do not edit. */\n\
+             #ifndef PXROS_SERVICE_COUNTER_H\n\
+             #define PXROS_SERVICE_COUNTER_H\n\
+             \n\
+             #include <stdint.h>\n\
+             \n\
+             #ifndef PXROS_RPC_HEADER\n\
+             #define PXROS_RPC_HEADER\n\
+             #define PXROS_RPC_KIND_SHIFT 0u\n\
+             #define PXROS_RPC_KIND_MASK 0xFFFFull\n\
+             #define PXROS_RPC_SEQ_SHIFT 16u\n\
+             #define PXROS_RPC_SEQ_MASK 0xFFFFull\n\
+             #define PXROS_RPC_FAILED_SHIFT 32u\n\
+             #define PXROS_RPC_FAILED_MASK 0x1ull\n\
+             #define PXROS_RPC_HAS_REPLY_MAILBOX_SHIFT 33u\n\
+             #define PXROS_RPC_HAS_REPLY_MAILBOX_MASK 0x1ull\n\
+             #define PXROS_RPC_REPLY_MAILBOX_SHIFT 34u\n\
+             #define PXROS_RPC_REPLY_MAILBOX_MASK 0xFFFFull\n\
+             #endif /* PXROS_RPC_HEADER */\n\
+             \n\
+             #define COUNTER_ADD 3u\n\
+             \n\
+             typedef struct {\n\
+             \x20   uint32_t amount;\n\
+             \x20   uint8_t tags[2];\n\
+             } counter_Add;\n\
+             \n\
+             typedef struct {\n\
+             \x20   uint64_t total;\n\
+             } counter_AddReply;\n\
+             \n\
+             /* Requests of every kind must be at least sizeof(counter_Request) bytes. */\n\
+             typedef union {\n\
+             \x20   counter_Add add;\n\
+             } counter_Request;\n\
+             \n\
+             /* Replies are sizeof(counter_Reply) bytes. */\n\
+             typedef union {\n\
+             \x20   counter_AddReply add;\n\
+             } counter_Reply;\n\
+             \n\
+             #endif /* PXROS_SERVICE_COUNTER_H */\n"
+        );
+    }
+
+    #[test]
+    fn dispatches_to_the_service_instance() {
+        let mut dispatcher = counter::Dispatcher(Total(10));
+        let request = counter::Request {
+            add: counter::Add {
+                amount: 5,
+                tags: [0; 2],
+            },
+        };
+        for total in [15, 20] {
+            let reply = dispatcher.dispatch(counter::ADD, &request).unwrap();
+            // # Safety
+            // Replies to `ADD` are built from the `add` variant.
+            assert_eq!(unsafe { reply.add }.total, total);
+        }
+        assert!(dispatcher.dispatch(4, &request).is_none());
+        assert_eq!(dispatcher.0 .0, 20);
+    }
+
+    #[test]
+    fn detects_duplicate_kinds() {
+        assert!(unique_kinds(&[1, 2, 3]));
+        assert!(!unique_kinds(&[1, 2, 1]));
+    }
+}
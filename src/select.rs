@@ -0,0 +1,192 @@
+//! Waiting on several mailboxes, events and a timeout at once.
+//!
+//! [Select] blocks the calling task until one of its sources fires and
+//! reports which one in [Selected]. At most one message is taken per wait,
+//! and events of sources that did not win are signalled to the task again,
+//! so nothing is consumed on behalf of a losing source.
+//!
+//! ```ignore
+//! let timeout = Timeout::new(Duration::from_millis(100), Events::from_bit(1))?;
+//! let select = Select::new(&[control, data], Events::from_bit(2));
+//! match select.events(shutdown).timeout(&timeout).wait::<Command>()? {
+//!     Selected::Message { index: 0, message } => handle_control(message),
+//!     Selected::Message { message, .. } => handle_data(message),
+//!     Selected::Events(_) => return,
+//!     Selected::Timeout => send_heartbeat(),
+//! }
+//! ```
+//!
+//! The kernel blocks on one mailbox at a time. While waiting, [Select]
+//! installs a mailbox handler with [PxMbxInstallHnd] on every mailbox, which
+//! signals the reserved notify event to the task whenever a message arrives,
+//! so every mailbox ends the wait.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::bindings::*;
+use crate::delay::Timeout;
+use crate::events::Events;
+use crate::msg::notify::Notify;
+use crate::msg::{Mailbox, Message, Plain, ReadOnly};
+use crate::PxResult;
+
+/// Source that ended a [`Select::wait`].
+pub enum Selected<T: Plain> {
+    /// A message arrived in the mailbox at `index`.
+    Message {
+        index: usize,
+        message: Message<T, ReadOnly>,
+    },
+    /// Some of the selected events arrived.
+    Events(Events),
+    /// The timeout elapsed.
+    Timeout,
+}
+
+/// Blocking wait on any combination of mailboxes, events and a timeout.
+#[derive(Copy, Clone)]
+pub struct Select<'a> {
+    mailboxes: &'a [Mailbox],
+    notify: Events,
+    events: Events,
+    timeout: Option<&'a Timeout>,
+}
+
+impl<'a> Select<'a> {
+    /// Selects on messages arriving in `mailboxes`, earlier mailboxes taking precedence.
+    ///
+    /// The mailbox handlers signal `notify` to the calling task. The events
+    /// are reserved for the select and must not be used otherwise by the task.
+    pub const fn new(mailboxes: &'a [Mailbox], notify: Events) -> Self {
+        Self {
+            mailboxes,
+            notify,
+            events: Events::NONE,
+            timeout: None,
+        }
+    }
+
+    /// Also selects on `events`.
+    pub fn events(mut self, events: Events) -> Self {
+        self.events |= events;
+        self
+    }
+
+    /// Ends the wait with [`Selected::Timeout`] once `timeout` elapsed.
+    ///
+    /// The timeout is restarted by every wait and stopped afterwards.
+    pub fn timeout(mut self, timeout: &'a Timeout) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Blocks until one of the sources fires.
+    ///
+    /// Messages are taken as `T`, a message not fitting `T` is released and
+    /// reported as error.
+    pub fn wait<T: Plain>(&self) -> PxResult<Selected<T>> {
+        if let Some(timeout) = self.timeout {
+            timeout.events().reset();
+            timeout.start()?;
+        }
+        let selected = self.wait_started();
+        if let Some(timeout) = self.timeout {
+            let _ = timeout.stop();
+            // A timeout that elapsed after another source won must not end the next wait.
+            timeout.events().reset();
+        }
+        selected
+    }
+
+    fn wait_started<T: Plain>(&self) -> PxResult<Selected<T>> {
+        debug_assert!(
+            !self.mailboxes.is_empty() || !self.sources().is_empty(),
+            "select without sources blocks forever"
+        );
+        debug_assert!(
+            self.mailboxes.is_empty() || !self.notify.is_empty(),
+            "select on mailboxes without notify event blocks forever"
+        );
+
+        let notify = Notify::current(self.notify);
+        let _installed = notify.install(self.mailboxes)?;
+        // Messages sent before the handlers were installed are found by the first check.
+        self.notify.reset();
+
+        let mask = self.sources() | self.notify;
+        let mut received = Events::NONE;
+        loop {
+            for (index, mailbox) in self.mailboxes.iter().enumerate() {
+                let handle = PxMsgReceive_NoWait(*mailbox).checked()?;
+                if handle.is_valid() {
+                    self.resignal(received);
+                    // # Safety
+                    // A received message is owned by the receiver only.
+                    let message = unsafe { Message::from_handle(handle) }?;
+                    return Ok(Selected::Message { index, message });
+                }
+            }
+            if let Some(selected) = self.select_events(received) {
+                return Ok(selected);
+            }
+            received = mask.await_any();
+        }
+    }
+
+    /// Picks the winner among the received events, preferring the selected events over the timeout.
+    fn select_events<T: Plain>(&self, received: Events) -> Option<Selected<T>> {
+        let events = received & self.events;
+        let timeout = received & self.timeout_events();
+        let (selected, winner) = if !events.is_empty() {
+            (Selected::Events(events), events)
+        } else if !timeout.is_empty() {
+            (Selected::Timeout, timeout)
+        } else {
+            return None;
+        };
+        self.resignal(received & !winner);
+        Some(selected)
+    }
+
+    /// Signals consumed events of losing sources back to the calling task.
+    fn resignal(&self, events: Events) {
+        let events = events & self.sources();
+        if !events.is_empty() {
+            let _ = events.signal(PxGetId());
+        }
+    }
+
+    /// Returns the selected events and the timeout events.
+    fn sources(&self) -> Events {
+        self.events | self.timeout_events()
+    }
+
+    fn timeout_events(&self) -> Events {
+        self.timeout.map_or(Events::NONE, Timeout::events)
+    }
+}
+
+impl<T: Plain> defmt::Format for Selected<T> {
+    fn format(&self, fmt: defmt::Formatter) {
+        match self {
+            Selected::Message { index, message } => {
+                defmt::write!(fmt, "Message({}, {})", index, message.handle())
+            }
+            Selected::Events(events) => defmt::write!(fmt, "Events({})", events),
+            Selected::Timeout => defmt::write!(fmt, "Timeout"),
+        }
+    }
+}
+
+impl defmt::Format for Select<'_> {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "Select(mailboxes: {}, events: {})",
+            self.mailboxes.len(),
+            self.events
+        )
+    }
+}
@@ -0,0 +1,178 @@
+//! Stack painting and high-water-mark measurement.
+//!
+//! Enabled with the `stack-paint` feature. Pre-allocated stacks built with
+//! [`Stack::from_static`](crate::mem::Stack::from_static) are filled with
+//! [PAINT] before the task starts.
+//! Tasks created with [TaskBuilder](crate::task::TaskBuilder) register their
+//! stacks automatically, tasks created otherwise are added with the unsafe
+//! [register]:
+//!
+//! ```ignore
+//! let stack = Stack::from_static(&mut CAN_STACK)?;
+//! let task = TaskBuilder::new(c"can", can_task, stack).create()?;
+//! // ...
+//! if let Some(usage) = stack::stack_high_water(task) {
+//!     defmt::info!("{}", usage);
+//! }
+//! ```
+//!
+//! Stacks allocated by the kernel from a memory class cannot be painted and
+//! are reported as not measured. Measuring the stacks of another task requires
+//! read access to them, see [stack_high_water].
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::bindings::*;
+use crate::mem::StackSpec;
+
+/// Pattern painted into unused stack words.
+pub const PAINT: u32 = 0xA5A5_A5A5;
+
+/// Number of tasks whose stacks can be registered.
+pub const MAX_TASKS: usize = 32;
+
+/// Error returned by [register] when all registry entries are taken.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct RegistryFull;
+
+/// Deepest usage of one stack in bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct StackUsage {
+    /// Bytes that were written at least once.
+    pub used: u32,
+    /// Size of the stack.
+    pub size: u32,
+}
+
+/// Deepest usage of the stacks of a task.
+///
+/// Stacks that were not painted are `None`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub struct StackHighWater {
+    /// Usage of the task stack.
+    pub task: Option<StackUsage>,
+    /// Usage of the interrupt stack.
+    pub interrupt: Option<StackUsage>,
+}
+
+/// Registers the stacks of `task` for [stack_high_water].
+///
+/// Registering a task again replaces its stacks.
+///
+/// # Safety
+/// Falling stacks among `task_stack` and `interrupt_stack` must reference
+/// memory that stays valid for reads for the rest of the program, as built by
+/// [`Stack::from_static`](crate::mem::Stack::from_static).
+/// [stack_high_water] scans it on every call.
+pub unsafe fn register(
+    task: PxTask_t,
+    task_stack: &StackSpec,
+    interrupt_stack: &StackSpec,
+) -> Result<(), RegistryFull> {
+    let raw = task.as_raw();
+    let entry = REGISTRY
+        .iter()
+        .find(|entry| entry.task.load(Ordering::Acquire) == raw)
+        .or_else(|| {
+            REGISTRY.iter().find(|entry| {
+                entry
+                    .claimed
+                    .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            })
+        })
+        .ok_or(RegistryFull)?;
+
+    entry.task_stack.store(task_stack);
+    entry.interrupt_stack.store(interrupt_stack);
+    entry.task.store(raw, Ordering::Release);
+    Ok(())
+}
+
+/// Returns the deepest stack usage of a registered task.
+///
+/// The stacks are scanned by the calling task, so its protection regions must
+/// grant read access to them. A task only having access to its own stack
+/// traps on the stacks of other tasks. Monitoring tasks get the stacks as an
+/// additional read-only region, see
+/// [TaskBuilder::regions](crate::task::TaskBuilder::regions).
+pub fn stack_high_water(task: PxTask_t) -> Option<StackHighWater> {
+    let entry = REGISTRY
+        .iter()
+        .find(|entry| entry.task.load(Ordering::Acquire) == task.as_raw())?;
+    Some(StackHighWater {
+        task: entry.task_stack.usage(),
+        interrupt: entry.interrupt_stack.usage(),
+    })
+}
+
+/// Fills the stack words with [PAINT].
+///
+/// # Safety
+/// `bottom` must be valid for writing `words` words and not in use as a stack.
+pub(crate) unsafe fn paint(bottom: *mut u32, words: usize) {
+    for index in 0..words {
+        bottom.add(index).write_volatile(PAINT);
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Entry = Entry {
+    claimed: AtomicBool::new(false),
+    task: AtomicU32::new(u32::MAX),
+    task_stack: PaintedStack::EMPTY,
+    interrupt_stack: PaintedStack::EMPTY,
+};
+
+static REGISTRY: [Entry; MAX_TASKS] = [EMPTY; MAX_TASKS];
+
+/// Registered stacks of a task.
+struct Entry {
+    claimed: AtomicBool,
+    task: AtomicU32,
+    task_stack: PaintedStack,
+    interrupt_stack: PaintedStack,
+}
+
+/// Location of a painted stack, zero words if the stack is not measured.
+struct PaintedStack {
+    bottom: AtomicU32,
+    words: AtomicU32,
+}
+
+impl PaintedStack {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: PaintedStack = PaintedStack {
+        bottom: AtomicU32::new(0),
+        words: AtomicU32::new(0),
+    };
+
+    fn store(&self, spec: &StackSpec) {
+        let (bottom, words) = spec.painted().unwrap_or((core::ptr::null(), 0));
+        self.bottom.store(bottom as usize as u32, Ordering::Relaxed);
+        self.words.store(words as u32, Ordering::Relaxed);
+    }
+
+    fn usage(&self) -> Option<StackUsage> {
+        let words = self.words.load(Ordering::Relaxed) as usize;
+        if words == 0 {
+            return None;
+        }
+        let bottom = self.bottom.load(Ordering::Relaxed) as usize as *const u32;
+        // Stacks fall, so untouched words remain at the bottom.
+        // # Safety
+        // `register` requires the stack to stay valid for reads.
+        let untouched = (0..words)
+            .take_while(|index| unsafe { bottom.add(*index).read_volatile() } == PAINT)
+            .count();
+        let word = core::mem::size_of::<u32>() as u32;
+        Some(StackUsage {
+            used: (words - untouched) as u32 * word,
+            size: words as u32 * word,
+        })
+    }
+}
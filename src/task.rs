@@ -0,0 +1,338 @@
+//! Task creation from a typed description.
+//!
+//! [TaskBuilder] fills a [PxTaskSpec_T] and creates the task with
+//! [PxTaskCreate]. Privileges and [AccessRights] are part of the builder, so
+//! the permissions of a task can be audited from its Rust definition:
+//!
+//! ```ignore
+//! static mut CAN_STACK: StackMemory<256> = StackMemory::new();
+//!
+//! let task = TaskBuilder::new(c"can", can_task, Stack::from_static(&mut CAN_STACK)?)
+//!     .prio(10)
+//!     .privileges(Privileges::DirectAccess)
+//!     .access_rights(AccessRights::INSTALL_HANDLERS | AccessRights::SYSTEMDEFAULT)
+//!     .regions(&CAN_REGIONS)
+//!     .create()?;
+//! ```
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ffi::CStr;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not};
+
+use crate::bindings::*;
+use crate::events::Events;
+use crate::mem::{Privileges, RegionSet, Stack};
+use crate::PxResult;
+
+/// Entry function of a task, called with its handle, mailbox and activation events.
+pub type TaskFn = extern "C" fn(PxTask_t, PxMbx_t, PxEvents_t);
+
+/// Kernel access rights of a task.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AccessRights(u32);
+
+impl AccessRights {
+    /// No access rights.
+    pub const NONE: AccessRights = AccessRights(0);
+    /// Use of interrupt and trap handlers.
+    pub const HANDLERS: AccessRights = AccessRights(PXACCESS_HANDLERS);
+    /// Installing interrupt and trap handlers.
+    pub const INSTALL_HANDLERS: AccessRights = AccessRights(PXACCESS_INSTALL_HANDLERS);
+    /// Installing system services.
+    pub const INSTALL_SERVICES: AccessRights = AccessRights(PXACCESS_INSTALL_SERVICES);
+    /// Access to peripheral registers through the kernel.
+    pub const REGISTERS: AccessRights = AccessRights(PXACCESS_REGISTERS);
+    /// Use of the system default object pool and memory class.
+    pub const SYSTEMDEFAULT: AccessRights = AccessRights(PXACCESS_SYSTEMDEFAULT);
+    /// Taking objects and memory from foreign pools and classes.
+    pub const RESOURCES: AccessRights = AccessRights(PXACCESS_RESOURCES);
+    /// Creating object pools and memory classes.
+    pub const NEW_RESOURCES: AccessRights = AccessRights(PXACCESS_NEW_RESOURCES);
+    /// Controlling the kernel, e.g. suspending scheduling.
+    pub const SYSTEM_CONTROL: AccessRights = AccessRights(PXACCESS_SYSTEM_CONTROL);
+    /// Changing the mode bits of the task.
+    pub const MODEBITS: AccessRights = AccessRights(PXACCESS_MODEBITS);
+    /// Creating tasks.
+    pub const TASK_CREATE: AccessRights = AccessRights(PXACCESS_TASK_CREATE);
+    /// Creating tasks with a higher priority than the own one.
+    pub const TASK_CREATE_HIGHER_PRIO: AccessRights =
+        AccessRights(PXACCESS_TASK_CREATE_HIGHER_PRIO);
+    /// Raising the priority of tasks above the own one.
+    pub const TASK_SET_HIGHER_PRIO: AccessRights = AccessRights(PXACCESS_TASK_SET_HIGHER_PRIO);
+    /// Changing the priority of tasks.
+    pub const CHANGE_PRIO: AccessRights = AccessRights(PXACCESS_CHANGE_PRIO);
+    /// Restoring the access rights of tasks.
+    pub const TASK_RESTORE_ACCESS_RIGHTS: AccessRights =
+        AccessRights(PXACCESS_TASK_RESTORE_ACCESS_RIGHTS);
+    /// Creating tasks with access rights the own task does not have.
+    pub const TASK_CREATE_HIGHER_ACCESS: AccessRights =
+        AccessRights(PXACCESS_TASK_CREATE_HIGHER_ACCESS);
+    /// Use of global objects.
+    pub const GLOBAL_OBJECTS: AccessRights = AccessRights(PXACCESS_GLOBAL_OBJECTS);
+
+    /// Named access rights, used for formatting.
+    const NAMED: [(AccessRights, &'static str); 16] = [
+        (Self::HANDLERS, "HANDLERS"),
+        (Self::INSTALL_HANDLERS, "INSTALL_HANDLERS"),
+        (Self::INSTALL_SERVICES, "INSTALL_SERVICES"),
+        (Self::REGISTERS, "REGISTERS"),
+        (Self::SYSTEMDEFAULT, "SYSTEMDEFAULT"),
+        (Self::RESOURCES, "RESOURCES"),
+        (Self::NEW_RESOURCES, "NEW_RESOURCES"),
+        (Self::SYSTEM_CONTROL, "SYSTEM_CONTROL"),
+        (Self::MODEBITS, "MODEBITS"),
+        (Self::TASK_CREATE, "TASK_CREATE"),
+        (Self::TASK_CREATE_HIGHER_PRIO, "TASK_CREATE_HIGHER_PRIO"),
+        (Self::TASK_SET_HIGHER_PRIO, "TASK_SET_HIGHER_PRIO"),
+        (Self::CHANGE_PRIO, "CHANGE_PRIO"),
+        (
+            Self::TASK_RESTORE_ACCESS_RIGHTS,
+            "TASK_RESTORE_ACCESS_RIGHTS",
+        ),
+        (Self::TASK_CREATE_HIGHER_ACCESS, "TASK_CREATE_HIGHER_ACCESS"),
+        (Self::GLOBAL_OBJECTS, "GLOBAL_OBJECTS"),
+    ];
+
+    /// Returns all access rights.
+    pub const fn all() -> Self {
+        let mut bits = 0;
+        let mut index = 0;
+        while index < Self::NAMED.len() {
+            bits |= Self::NAMED[index].0 .0;
+            index += 1;
+        }
+        AccessRights(bits)
+    }
+
+    /// Creates access rights from raw bits, dropping unknown bits.
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        AccessRights(bits & Self::all().0)
+    }
+
+    /// Returns the raw bits.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Checks if no access right is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Checks if all rights in `other` are set.
+    pub const fn contains(&self, other: AccessRights) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the rights of `self` and `other`, usable in `const` contexts.
+    pub const fn union(self, other: AccessRights) -> Self {
+        AccessRights(self.0 | other.0)
+    }
+}
+
+impl BitOr for AccessRights {
+    type Output = AccessRights;
+
+    fn bitor(self, rhs: AccessRights) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for AccessRights {
+    fn bitor_assign(&mut self, rhs: AccessRights) {
+        *self = *self | rhs;
+    }
+}
+
+impl BitAnd for AccessRights {
+    type Output = AccessRights;
+
+    fn bitand(self, rhs: AccessRights) -> Self::Output {
+        AccessRights(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for AccessRights {
+    fn bitand_assign(&mut self, rhs: AccessRights) {
+        *self = *self & rhs;
+    }
+}
+
+impl Not for AccessRights {
+    type Output = AccessRights;
+
+    fn not(self) -> Self::Output {
+        Self::from_bits_truncate(!self.0)
+    }
+}
+
+impl defmt::Format for AccessRights {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "AccessRights(");
+        let mut first = true;
+        for (rights, name) in Self::NAMED {
+            if self.contains(rights) {
+                if !first {
+                    defmt::write!(fmt, " | ");
+                }
+                defmt::write!(fmt, "{=str}", name);
+                first = false;
+            }
+        }
+        defmt::write!(fmt, ")")
+    }
+}
+
+/// Builder for a PXROS task.
+///
+/// Unless configured otherwise, the task runs without privileges and access
+/// rights, takes its objects and memory from the default task pool and class,
+/// and shares its task stack for interrupts.
+#[must_use = "The task is only created by `create`"]
+pub struct TaskBuilder {
+    name: &'static CStr,
+    entry: TaskFn,
+    prio: u32,
+    events: Events,
+    mc: PxMc_t,
+    opool: PxOpool_t,
+    stack: Stack,
+    interrupt_stack: Stack,
+    privileges: Privileges,
+    access_rights: AccessRights,
+    context: *const PxTaskContext_T,
+    regions: *const PxProtectRegion_T,
+}
+
+impl TaskBuilder {
+    /// Starts building the task `name` running `entry` on `stack`.
+    pub fn new(name: &'static CStr, entry: TaskFn, stack: Stack) -> Self {
+        Self {
+            name,
+            entry,
+            prio: 0,
+            events: Events::NONE,
+            mc: PxMc_t::default(),
+            opool: PxOpool_t::default(),
+            stack,
+            interrupt_stack: Stack::dont_check(),
+            privileges: Privileges::NoDirectAccess,
+            access_rights: AccessRights::NONE,
+            context: core::ptr::null(),
+            regions: core::ptr::null(),
+        }
+    }
+
+    /// Sets the priority, lower values take precedence.
+    pub fn prio(mut self, prio: u32) -> Self {
+        self.prio = prio;
+        self
+    }
+
+    /// Sets the events the task waits for before it runs.
+    pub fn activation_events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Sets the memory class the task allocates from.
+    pub fn memory_class(mut self, mc: impl Into<PxMc_t>) -> Self {
+        self.mc = mc.into();
+        self
+    }
+
+    /// Sets the object pool the task and its objects are taken from.
+    pub fn opool(mut self, opool: impl Into<PxOpool_t>) -> Self {
+        self.opool = opool.into();
+        self
+    }
+
+    /// Uses a separate stack for interrupts.
+    pub fn interrupt_stack(mut self, stack: Stack) -> Self {
+        self.interrupt_stack = stack;
+        self
+    }
+
+    /// Sets the privileges for accessing peripherals.
+    pub fn privileges(mut self, privileges: Privileges) -> Self {
+        self.privileges = privileges;
+        self
+    }
+
+    /// Sets the kernel access rights.
+    pub fn access_rights(mut self, access_rights: AccessRights) -> Self {
+        self.access_rights = access_rights;
+        self
+    }
+
+    /// Sets the task context describing its protected data.
+    pub fn context(mut self, context: &'static PxTaskContext_T) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Sets the additional protection regions of the task.
+    pub fn regions<const N: usize>(mut self, regions: &'static RegionSet<N>) -> Self {
+        self.regions = regions.as_ptr();
+        self
+    }
+
+    /// Returns the task spec passed to the kernel.
+    pub fn spec(&self) -> PxTaskSpec_T {
+        // # Safety
+        // The spec is plain data, zero is valid for every field not set below.
+        let mut spec: PxTaskSpec_T = unsafe { core::mem::zeroed() };
+        spec.ts_name = self.name.as_ptr();
+        // The safe entry function coerces to the unsafe pointer of the spec.
+        spec.ts_fun = Some(self.entry);
+        spec.ts_mc = self.mc;
+        spec.ts_opool = self.opool;
+        spec.ts_taskstack = *self.stack.spec();
+        spec.ts_inttaskstack = *self.interrupt_stack.spec();
+        spec.ts_prio = self.prio as _;
+        spec.ts_actevents = self.events;
+        spec.ts_privileges = self.privileges as _;
+        spec.ts_accessrights = self.access_rights.bits() as _;
+        spec.ts_context = self.context;
+        spec.ts_protect_region = self.regions;
+        spec
+    }
+
+    /// Creates the task, taking the task object from the configured object pool.
+    ///
+    /// With the `stack-paint` feature the stacks are registered for
+    /// [stack_high_water](crate::stack::stack_high_water). If the registry is
+    /// full, a warning is logged and the task is created without measurement.
+    pub fn create(self) -> PxResult<PxTask_t> {
+        let spec = self.spec();
+        // # Safety
+        // The spec only references `'static` data and is copied by PXROS.
+        let task = unsafe { PxTaskCreate(self.opool, &spec, PxPrio_t(self.prio), self.events) }
+            .checked()?;
+        // # Safety
+        // The kernel runs the task on these stacks, so they are `'static` like the spec.
+        #[cfg(feature = "stack-paint")]
+        if let Err(full) =
+            unsafe { crate::stack::register(task, &spec.ts_taskstack, &spec.ts_inttaskstack) }
+        {
+            defmt::warn!("Stacks of task {} not measured: {}", task, full);
+        }
+        Ok(task)
+    }
+}
+
+impl defmt::Format for TaskBuilder {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "TaskBuilder({=str}, prio: {}, privileges: {}, access: {})",
+            self.name.to_str().unwrap_or("?"),
+            self.prio,
+            self.privileges,
+            self.access_rights
+        )
+    }
+}
@@ -0,0 +1,83 @@
+//! Periodic events [PxPe_t].
+//!
+//! A [Ticker] owns a periodic event object that signals its events to the
+//! requesting task every period.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::bindings::*;
+use crate::events::Events;
+use crate::time::Duration;
+use crate::PxResult;
+
+/// Owned periodic event, released on drop.
+///
+/// The ticker is created stopped.
+#[must_use = "Dropping a ticker releases its periodic event"]
+pub struct Ticker {
+    handle: PxPe_t,
+    events: Events,
+}
+
+impl Ticker {
+    /// Requests a periodic event signalling `events` every `period`.
+    pub fn new(opool: impl Into<PxOpool_t>, period: Duration, events: Events) -> PxResult<Self> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        let handle = unsafe { PxPeRequest(opool.into(), period.into(), events) }.checked()?;
+        Ok(Self { handle, events })
+    }
+
+    /// Returns the raw periodic event handle.
+    pub const fn handle(&self) -> PxPe_t {
+        self.handle
+    }
+
+    /// Returns the events signalled every period.
+    pub const fn events(&self) -> Events {
+        self.events
+    }
+
+    /// Starts the ticker.
+    ///
+    /// Pending ticker events are reset, so the first period starts now.
+    pub fn start(&self) -> PxResult<()> {
+        self.events.reset();
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxPeStart(self.handle) }.into()
+    }
+
+    /// Stops the ticker.
+    pub fn stop(&self) -> PxResult<()> {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        unsafe { PxPeStop(self.handle) }.into()
+    }
+
+    /// Blocks until the next period elapsed.
+    ///
+    /// Returns the ticker events that arrived.
+    pub fn wait_next(&self) -> Events {
+        self.events.await_any()
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        // # Safety
+        // The handle is owned by this ticker and not used afterwards.
+        unsafe {
+            let _ = PxPeStop(self.handle);
+            let _ = PxPeRelease(self.handle);
+        }
+    }
+}
+
+impl defmt::Format for Ticker {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "Ticker({}, events: {})", self.handle, self.events)
+    }
+}
@@ -0,0 +1,287 @@
+//! Monotonic time based on the kernel tick counter [PxTicks_t].
+//!
+//! [Instant] and [Duration] count kernel ticks. The tick rate is configured
+//! when the kernel is initialized, so conversions from and to milliseconds
+//! and microseconds query it from the kernel.
+//!
+//! The tick counter is 32 bits wide and wraps around. Arithmetic on [Instant]
+//! wraps accordingly, so instants can be compared and subtracted as long as
+//! they are less than half the counter range apart.
+//!
+//! SPDX-FileCopyrightText: Veecle GmbH, HighTec EDV-Systeme GmbH
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::cmp::Ordering;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+use crate::bindings::*;
+
+/// Returns the number of kernel ticks per second.
+pub fn ticks_per_second() -> u32 {
+    // # Safety
+    // Parameters are copied and checked by PXROS.
+    unsafe { PxTickGetTicksFromMilliSeconds(1000) }.0
+}
+
+/// Span of time in kernel ticks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, defmt::Format)]
+pub struct Duration {
+    ticks: u32,
+}
+
+impl Duration {
+    /// Empty duration.
+    pub const ZERO: Duration = Duration::from_ticks(0);
+    /// Longest representable duration.
+    pub const MAX: Duration = Duration::from_ticks(u32::MAX);
+
+    /// Creates a duration of `ticks` kernel ticks.
+    pub const fn from_ticks(ticks: u32) -> Self {
+        Self { ticks }
+    }
+
+    /// Creates a duration from milliseconds.
+    pub fn from_millis(millis: u32) -> Self {
+        // # Safety
+        // Parameters are copied and checked by PXROS.
+        Self::from_ticks(unsafe { PxTickGetTicksFromMilliSeconds(millis) }.0)
+    }
+
+    /// Creates a duration from microseconds, rounding up to the next full tick.
+    pub fn from_micros(micros: u32) -> Self {
+        Self::from_scaled(micros as u64, 1_000_000)
+    }
+
+    /// Returns the number of kernel ticks.
+    pub const fn as_ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Returns the duration in whole milliseconds.
+    pub fn as_millis(&self) -> u64 {
+        self.ticks as u64 * 1_000 / ticks_per_second() as u64
+    }
+
+    /// Returns the duration in whole microseconds.
+    pub fn as_micros(&self) -> u64 {
+        self.ticks as u64 * 1_000_000 / ticks_per_second() as u64
+    }
+
+    /// Checks if the duration is zero ticks long.
+    pub const fn is_zero(&self) -> bool {
+        self.ticks == 0
+    }
+
+    /// Adds two durations, returning `None` on overflow.
+    pub const fn checked_add(self, rhs: Duration) -> Option<Duration> {
+        match self.ticks.checked_add(rhs.ticks) {
+            Some(ticks) => Some(Duration::from_ticks(ticks)),
+            None => None,
+        }
+    }
+
+    /// Subtracts two durations, returning `None` if `rhs` is longer.
+    pub const fn checked_sub(self, rhs: Duration) -> Option<Duration> {
+        match self.ticks.checked_sub(rhs.ticks) {
+            Some(ticks) => Some(Duration::from_ticks(ticks)),
+            None => None,
+        }
+    }
+
+    /// Subtracts two durations, returning [`Duration::ZERO`] if `rhs` is longer.
+    pub const fn saturating_sub(self, rhs: Duration) -> Duration {
+        Duration::from_ticks(self.ticks.saturating_sub(rhs.ticks))
+    }
+
+    /// Converts `value` units of `1 / units_per_second` seconds, rounding up and saturating.
+    fn from_scaled(value: u64, units_per_second: u64) -> Self {
+        let ticks = (value as u128 * ticks_per_second() as u128).div_ceil(units_per_second as u128);
+        Self::from_ticks(ticks.min(u32::MAX as u128) as u32)
+    }
+}
+
+impl From<core::time::Duration> for Duration {
+    /// Converts the duration rounding up to the next full tick and saturating at [`Duration::MAX`].
+    fn from(duration: core::time::Duration) -> Self {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        Self::from_scaled(nanos, 1_000_000_000)
+    }
+}
+
+impl From<Duration> for PxTicks_t {
+    fn from(duration: Duration) -> Self {
+        PxTicks_t(duration.ticks)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add(rhs)
+            .expect("overflow when adding durations")
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub(rhs)
+            .expect("overflow when subtracting durations")
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+/// Point in time of the kernel tick counter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, defmt::Format)]
+pub struct Instant {
+    ticks: u32,
+}
+
+impl Instant {
+    /// Returns the current value of the kernel tick counter.
+    pub fn now() -> Self {
+        // # Safety
+        // Takes no parameters.
+        Self::from_ticks(unsafe { PxTickGetCount() }.0)
+    }
+
+    /// Creates an instant from a raw tick count.
+    pub const fn from_ticks(ticks: u32) -> Self {
+        Self { ticks }
+    }
+
+    /// Returns the raw tick count.
+    pub const fn as_ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Returns the time elapsed since `earlier`.
+    ///
+    /// Returns [`Duration::ZERO`] if `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns the time elapsed since `earlier`, or `None` if `earlier` is later than `self`.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        let ticks = self.ticks.wrapping_sub(earlier.ticks);
+        if (ticks as i32) < 0 {
+            None
+        } else {
+            Some(Duration::from_ticks(ticks))
+        }
+    }
+
+    /// Returns the time elapsed since this instant.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+}
+
+impl PartialOrd for Instant {
+    /// Orders instants by their wrapping distance.
+    ///
+    /// Instants exactly half the counter range apart are unordered.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.ticks.wrapping_sub(other.ticks) as i32 {
+            i32::MIN => None,
+            distance => Some(distance.cmp(&0)),
+        }
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Instant::from_ticks(self.ticks.wrapping_add(rhs.ticks))
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        Instant::from_ticks(self.ticks.wrapping_sub(rhs.ticks))
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Self::Output {
+        self.duration_since(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instants_order_across_wrap_around() {
+        let before = Instant::from_ticks(u32::MAX - 1);
+        let after = before + Duration::from_ticks(3);
+        assert_eq!(after.as_ticks(), 1);
+        assert!(before < after);
+        assert!(after > before);
+        assert_eq!(before.partial_cmp(&before), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn instants_half_the_range_apart_flip_order() {
+        let start = Instant::from_ticks(0);
+        assert!(start < start + Duration::from_ticks(i32::MAX as u32));
+        assert!(start > start + Duration::from_ticks(i32::MAX as u32 + 2));
+        let half = start + Duration::from_ticks(i32::MAX as u32 + 1);
+        assert_eq!(start.partial_cmp(&half), None);
+        assert_eq!(half.partial_cmp(&start), None);
+    }
+
+    #[test]
+    fn checked_duration_since_wraps() {
+        let before = Instant::from_ticks(u32::MAX - 1);
+        let after = Instant::from_ticks(4);
+        assert_eq!(
+            after.checked_duration_since(before),
+            Some(Duration::from_ticks(6))
+        );
+        assert_eq!(before.checked_duration_since(after), None);
+        assert_eq!(before.duration_since(after), Duration::ZERO);
+        assert_eq!(after - before, Duration::from_ticks(6));
+        assert_eq!(after.checked_duration_since(after), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn subtracting_durations_wraps() {
+        let instant = Instant::from_ticks(2) - Duration::from_ticks(5);
+        assert_eq!(instant.as_ticks(), u32::MAX - 2);
+    }
+}